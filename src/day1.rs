@@ -3,27 +3,117 @@ use std::fs;
 
 pub fn day1a() -> String {
     let values = read_data();
-    find_expenses(2, &values)
+    find_expenses(2, 2020, &values)
 }
 
 pub fn day1b() -> String {
     let values = read_data();
-    find_expenses(3, &values)
-}
-
-fn find_expenses(n: usize, values: &[usize]) -> String {
-    match values
-        .iter()
-        .combinations(n)
-        .find(|v| v.iter().copied().sum::<usize>() == 2020)
-        .map(|v| v.into_iter().product::<usize>())
-        .map(|v| v.to_string())
-    {
-        Some(v) => v,
+    find_expenses(3, 2020, &values)
+}
+
+/// Lists every distinct set of `k` expenses that add up to `target`, along with their product
+pub fn day1ksum(args: &[String]) -> String {
+    let parsed = (args.first().map(|s| s.parse::<usize>()), args.get(1).map(|s| s.parse::<usize>()));
+    let (k, target) = match parsed {
+        (Some(Ok(k)), Some(Ok(target))) if k > 0 && args.len() == 2 => (k, target),
+        _ => return "Usage: day1ksum <k of at least 1> <target>".to_string(),
+    };
+    let values = read_data();
+    let results = k_sum(&values, k, target);
+    if results.is_empty() {
+        return "No solution".to_string();
+    }
+    results.iter()
+        .map(|combo| format!("{} => {}", combo.iter().join(" + "), describe_product(combo)))
+        .join("\n")
+}
+
+fn find_expenses(k: usize, target: usize, values: &[usize]) -> String {
+    match k_sum(values, k, target).first() {
+        Some(combo) => describe_product(combo),
         None => "No solution".to_string(),
     }
 }
 
+fn describe_product(combo: &[usize]) -> String {
+    match checked_product(combo) {
+        Some(v) => v.to_string(),
+        None => "Product overflows".to_string(),
+    }
+}
+
+/// The product of `values`, or `None` if it doesn't fit in a `usize`
+pub fn checked_product(values: &[usize]) -> Option<usize> {
+    values.iter().try_fold(1usize, |acc, &v| acc.checked_mul(v))
+}
+
+/// Every distinct combination of `k` values that add up to `target`, each sorted, in ascending order
+pub fn k_sum(values: &[usize], k: usize, target: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let sorted = values.iter().copied().sorted().collect::<Vec<usize>>();
+    let mut results = Vec::new();
+    let mut prefix = Vec::with_capacity(k);
+    k_sum_sorted(&sorted, k, target, &mut prefix, &mut results);
+    results
+}
+
+fn k_sum_sorted(values: &[usize], k: usize, target: usize, prefix: &mut Vec<usize>, results: &mut Vec<Vec<usize>>) {
+    match k {
+        0 => {
+            if target == 0 {
+                results.push(prefix.clone());
+            }
+        }
+        1 => {
+            if values.binary_search(&target).is_ok() {
+                prefix.push(target);
+                results.push(prefix.clone());
+                prefix.pop();
+            }
+        }
+        2 => {
+            if values.len() < 2 {
+                return;
+            }
+            let (mut lo, mut hi) = (0, values.len() - 1);
+            while lo < hi {
+                // A sum that overflows is certainly too large
+                let sum = values[lo].checked_add(values[hi]);
+                if sum.is_some_and(|sum| sum < target) {
+                    lo += 1;
+                } else if sum.is_none_or(|sum| sum > target) {
+                    hi -= 1;
+                } else {
+                    let mut combo = prefix.clone();
+                    combo.push(values[lo]);
+                    combo.push(values[hi]);
+                    results.push(combo);
+                    // Skip over duplicates so that each combination is only reported once
+                    let (a, b) = (values[lo], values[hi]);
+                    while lo < hi && values[lo] == a { lo += 1; }
+                    while lo < hi && values[hi] == b { hi -= 1; }
+                }
+            }
+        }
+        _ => {
+            for (i, &v) in values.iter().enumerate() {
+                if i > 0 && values[i - 1] == v {
+                    continue;
+                }
+                // The values are sorted, so once the smallest k values overshoot, nothing further along will fit
+                if v.saturating_mul(k) > target {
+                    break;
+                }
+                prefix.push(v);
+                k_sum_sorted(&values[i + 1..], k - 1, target - v, prefix, results);
+                prefix.pop();
+            }
+        }
+    }
+}
+
 fn read_data() -> Vec<usize> {
     let values = fs::read_to_string("assets/day1a.txt").expect("Could not load file");
     values
@@ -1,7 +1,9 @@
 use std::fs;
+use itertools::Itertools;
+use rand::Rng;
 
 pub fn day10a() -> String {
-    let adapters = read_data();
+    let adapters = read_data(&ChainRules::default());
     let (ones, threes) = &adapters.windows(2)
         .fold((0, 0), |(ones, threes), v| {
             match v[1] - v[0] {
@@ -14,57 +16,210 @@ pub fn day10a() -> String {
 }
 
 pub fn day10b() -> String {
-    let adapters = read_data();
-    let mut result = Vec::new();
-    // Scan the array, looking for 3-step jumps
-    let mut start = 0;
-    let mut end = 0;
-    while end < adapters.len() - 1 {
-        if adapters[end + 1] - adapters[end] == 3 {
-            let count = count_configurations(&adapters[start..end + 1]);
-            // println!("start: {}, end: {}, count: {}, seq: {:?}", start, end, count, &adapters[start..end + 1]);
-            result.push(count);
-            start = end + 1;
+    let rules = ChainRules::default();
+    let adapters = read_data(&rules);
+    describe_count(count_arrangements(&adapters, rules.max_gap))
+}
+
+/// Counts the arrangements with a different maximum gap and device offset, which both default to 3
+pub fn day10count(args: &[String]) -> String {
+    let parse = |i: usize| args.get(i).map(|s| s.parse::<usize>());
+    let rules = match (parse(0), parse(1)) {
+        (None, _) => ChainRules::default(),
+        (Some(Ok(max_gap)), None) => ChainRules { max_gap, ..ChainRules::default() },
+        (Some(Ok(max_gap)), Some(Ok(device_offset))) => ChainRules { max_gap, device_offset },
+        _ => return "Usage: day10count [max_gap] [device_offset]".to_string(),
+    };
+    let adapters = read_data(&rules);
+    describe_count(count_arrangements(&adapters, rules.max_gap))
+}
+
+/// Shows the first `n` chains, the `n`-th chain, or `n` random chains
+pub fn day10chains(args: &[String]) -> String {
+    let usage = "Usage: day10chains list|nth|sample <n>".to_string();
+    let n = match args.get(1).map(|s| s.parse::<u128>()) {
+        Some(Ok(n)) => n,
+        _ => return usage,
+    };
+    let rules = ChainRules::default();
+    let chains = AdapterChains::new(read_data(&rules), rules.max_gap);
+    let show = |chain: Vec<usize>| chain.iter().join(",");
+    match args[0].as_str() {
+        "list" => chains.iter().take(n as usize).map(show).join("\n"),
+        "nth" => chains.nth_chain(n).map(show).unwrap_or_else(|| format!("There are only {} chains", chains.count())),
+        "sample" => {
+            let mut rng = rand::thread_rng();
+            (0..n).filter_map(|_| chains.sample(&mut rng)).map(show).join("\n")
         }
-        end += 1;
+        _ => usage,
     }
-    result.iter().product::<usize>().to_string()
 }
 
-fn validate_slice(values: &[usize]) -> bool {
-    values.windows(2).all(|v| v[1] - v[0] <= 3)
+fn describe_count(count: Option<u128>) -> String {
+    match count {
+        Some(n) => n.to_string(),
+        None => "Too many arrangements to count".to_string(),
+    }
+}
+
+/// How far below its rating an adapter can take its input, and how far above the highest adapter the device is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainRules {
+    pub max_gap: usize,
+    pub device_offset: usize,
 }
 
-// Convert val to a binary bitmap and select the elements of values that have the bits set
-fn select_filter(val: usize, values: &[usize]) -> Vec<usize> {
-    values.iter()
-        .enumerate()
-        .filter(|(i, _)| {
-            // Is the ith bit of val set?
-            val & (1 << *i) > 0
-        })
-        .map(|(_, v)| *v)
-        .collect()
+impl Default for ChainRules {
+    fn default() -> Self {
+        Self { max_gap: 3, device_offset: 3 }
+    }
 }
 
-fn count_configurations(values: &[usize]) -> usize {
-    if values.len() < 3 { return 1; }
-    let mut result = 0usize;
-    let n = 1 << (values.len() - 2);
-    let sub_slice = &values[1..values.len()-1];
-    for v in 0..n {
-        let mut config = vec![values[0]];
-        config.append(&mut select_filter(v, sub_slice));
-        config.push(*values.last().unwrap());
-        if validate_slice(&config) {
-            result += 1;
+/// Counts the chains through the sorted `joltages` with no step larger than `max_gap`, if that fits in a `u128`
+pub fn count_arrangements(joltages: &[usize], max_gap: usize) -> Option<u128> {
+    if joltages.is_empty() {
+        return Some(0);
+    }
+    // The number of chains that end at each adapter
+    let mut ways = vec![0u128; joltages.len()];
+    ways[0] = 1;
+    for i in 1..joltages.len() {
+        let mut total = 0u128;
+        for j in (0..i).rev().take_while(|&j| joltages[i] - joltages[j] <= max_gap) {
+            total = total.checked_add(ways[j])?;
+        }
+        ways[i] = total;
+    }
+    ways.last().copied()
+}
+
+/// The adapter chains for a sorted list of joltages, ordered by adapter position rather than by joltage
+pub struct AdapterChains {
+    joltages: Vec<usize>,
+    max_gap: usize,
+    // Chains from each adapter to the end, saturating, which keeps `nth_chain` right below the saturated total
+    counts: Vec<u128>,
+}
+
+impl AdapterChains {
+    pub fn new(joltages: Vec<usize>, max_gap: usize) -> Self {
+        let n = joltages.len();
+        let mut counts = vec![0u128; n];
+        if let Some(last) = counts.last_mut() {
+            *last = 1;
+        }
+        for i in (0..n.saturating_sub(1)).rev() {
+            counts[i] = (i + 1..n)
+                .take_while(|&j| joltages[j] - joltages[i] <= max_gap)
+                .fold(0u128, |tot, j| tot.saturating_add(counts[j]));
+        }
+        Self { joltages, max_gap, counts }
+    }
+
+    pub fn count(&self) -> u128 {
+        self.counts.first().copied().unwrap_or(0)
+    }
+
+    /// The adapters that can follow adapter `i` and still reach the end, in order
+    fn next_adapters(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        (i + 1..self.joltages.len())
+            .take_while(move |&j| self.joltages[j] - self.joltages[i] <= self.max_gap)
+            .filter(move |&j| self.counts[j] > 0)
+    }
+
+    fn to_joltages(&self, path: &[usize]) -> Vec<usize> {
+        path.iter().map(|&i| self.joltages[i]).collect()
+    }
+
+    /// Every chain, lazily
+    pub fn iter(&self) -> Chains<'_> {
+        Chains { chains: self, path: None, done: self.count() == 0 }
+    }
+
+    /// The `k`-th chain, counting from 0
+    pub fn nth_chain(&self, mut k: u128) -> Option<Vec<usize>> {
+        if k >= self.count() {
+            return None;
+        }
+        let mut path = vec![0];
+        let last = self.joltages.len() - 1;
+        while *path.last().unwrap() != last {
+            let i = *path.last().unwrap();
+            for j in self.next_adapters(i) {
+                if k < self.counts[j] {
+                    path.push(j);
+                    break;
+                }
+                k -= self.counts[j];
+            }
+        }
+        Some(self.to_joltages(&path))
+    }
+
+    /// A chain picked uniformly at random
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<Vec<usize>> {
+        match self.count() {
+            0 => None,
+            n => self.nth_chain(rng.gen_range(0..n)),
         }
     }
-    result
 }
 
+/// An iterator over every adapter chain, built from `AdapterChains::iter`
+pub struct Chains<'a> {
+    chains: &'a AdapterChains,
+    path: Option<Vec<usize>>,
+    done: bool,
+}
+
+impl Chains<'_> {
+    /// Extends `path` with the first adapter that can follow it until it reaches the end
+    fn complete(&self, path: &mut Vec<usize>) {
+        let last = self.chains.joltages.len() - 1;
+        while *path.last().unwrap() != last {
+            let next = self.chains.next_adapters(*path.last().unwrap()).next().unwrap();
+            path.push(next);
+        }
+    }
+}
+
+impl Iterator for Chains<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut path = match self.path.take() {
+            None => vec![0],
+            Some(mut path) => {
+                // Find the last adapter that has a later alternative for the adapter after it, and switch to that
+                let advanced = (0..path.len() - 1).rev().find_map(|p| {
+                    let current = path[p + 1];
+                    self.chains.next_adapters(path[p]).find(|&j| j > current).map(|j| (p, j))
+                });
+                match advanced {
+                    Some((p, j)) => {
+                        path.truncate(p + 1);
+                        path.push(j);
+                        path
+                    }
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
+                }
+            }
+        };
+        self.complete(&mut path);
+        let result = self.chains.to_joltages(&path);
+        self.path = Some(path);
+        Some(result)
+    }
+}
 
-fn read_data() -> Vec<usize> {
+/// The sorted adapter ratings, with the outlet (0) at the start and the device at the end
+fn read_data(rules: &ChainRules) -> Vec<usize> {
     let mut result = vec![0usize];
     let values = fs::read_to_string("assets/day10.txt").expect("Could not load file");
     values
@@ -73,7 +228,73 @@ fn read_data() -> Vec<usize> {
         .for_each(|v| result.push(v));
     result.sort_unstable();
     // Push the device joltage
-    result.push(result[result.len() - 1] + 3);
+    result.push(result[result.len() - 1] + rules.device_offset);
     result
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Every chain by trying each later adapter in turn, in order of adapter positions
+    fn all_chains(joltages: &[usize], max_gap: usize) -> Vec<Vec<usize>> {
+        fn extend(joltages: &[usize], max_gap: usize, path: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+            let i = *path.last().unwrap();
+            if i == joltages.len() - 1 {
+                out.push(path.iter().map(|&p| joltages[p]).collect());
+                return;
+            }
+            for j in i + 1..joltages.len() {
+                if joltages[j] - joltages[i] <= max_gap {
+                    path.push(j);
+                    extend(joltages, max_gap, path, out);
+                    path.pop();
+                }
+            }
+        }
+        let mut out = Vec::new();
+        if !joltages.is_empty() {
+            extend(joltages, max_gap, &mut vec![0], &mut out);
+        }
+        out
+    }
+
+    fn check(joltages: Vec<usize>, max_gap: usize) {
+        let expected = all_chains(&joltages, max_gap);
+        let n = expected.len() as u128;
+        assert_eq!(count_arrangements(&joltages, max_gap), Some(n), "{:?} gap {}", joltages, max_gap);
+        let chains = AdapterChains::new(joltages.clone(), max_gap);
+        assert_eq!(chains.count(), n, "{:?} gap {}", joltages, max_gap);
+        assert_eq!(chains.iter().collect::<Vec<_>>(), expected, "{:?} gap {}", joltages, max_gap);
+        let nth = (0..=n).map(|k| chains.nth_chain(k)).collect::<Vec<_>>();
+        assert_eq!(nth[..n as usize], expected.iter().cloned().map(Some).collect::<Vec<_>>()[..]);
+        assert_eq!(nth[n as usize], None);
+    }
+
+    #[test]
+    fn duplicates_order_by_position() {
+        check(vec![0, 1, 1, 1, 2], 1);
+        let chains = AdapterChains::new(vec![0, 1, 1, 1, 2], 1);
+        assert_eq!(chains.iter().collect::<Vec<_>>(), vec![
+            vec![0, 1, 1, 1, 2],
+            vec![0, 1, 1, 2],
+            vec![0, 1, 1, 2],
+            vec![0, 1, 2],
+            vec![0, 1, 1, 2],
+            vec![0, 1, 2],
+            vec![0, 1, 2],
+        ]);
+    }
+
+    #[test]
+    fn chains_agree_with_brute_force() {
+        let mut rng = StdRng::seed_from_u64(10);
+        for _ in 0..500 {
+            let len = rng.gen_range(1..9);
+            let mut joltages = (0..len).map(|_| rng.gen_range(0..10)).collect::<Vec<usize>>();
+            joltages.sort_unstable();
+            check(joltages, rng.gen_range(1..4));
+        }
+    }
+}
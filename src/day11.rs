@@ -1,28 +1,158 @@
 use std::fmt::{Display, Formatter};
 use std::fs;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crate::render::{Animator, Cell, Frame};
 
 pub fn day11a() -> String {
     let layout = read_data();
-    let result = layout.simulate(Layout::count_adjacent, 4);
-    result.count_occupied().to_string()
+    let outcome = layout.simulate(&SeatingRules::adjacent(), MAX_STEPS);
+    describe_outcome(&outcome)
 }
 
 pub fn day11b() -> String {
     let layout = read_data();
-    let result = layout.simulate(Layout::count_visible, 5);
-    result.count_occupied().to_string()
+    let outcome = layout.simulate(&SeatingRules::visible(), MAX_STEPS);
+    describe_outcome(&outcome)
 }
 
-type CountFn = fn(&Layout, usize, usize) -> usize;
+/// Animates the simulation, taking the `Animator::from_args` options along with any seating rule options
+pub fn day11animate(args: &[String]) -> String {
+    let (mut animator, rest) = match Animator::from_args(args) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let rules = match SeatingRules::from_args(&rest) {
+        Ok(r) => r,
+        Err(e) => return e,
+    };
+    let mut error = None;
+    let outcome = read_data().simulate_with(&rules, MAX_STEPS, |layout| {
+        if error.is_none() {
+            error = animator.draw(layout).err();
+        }
+    });
+    match error {
+        Some(e) => format!("Could not draw frame: {}", e),
+        None => format!("{} frames drawn\n{}", animator.frames(), describe_outcome(&outcome)),
+    }
+}
+
+/// Runs the simulation with the rules given by `SeatingRules::from_args`
+pub fn day11rules(args: &[String]) -> String {
+    let rules = match SeatingRules::from_args(args) {
+        Ok(r) => r,
+        Err(e) => return e,
+    };
+    let layout = read_data();
+    describe_outcome(&layout.simulate(&rules, MAX_STEPS))
+}
+
+const MAX_STEPS: usize = 10_000;
+
+fn describe_outcome(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Stable { layout, .. } => layout.count_occupied().to_string(),
+        Outcome::Cycle { start, period, .. } => {
+            format!("No stable layout: the seats repeat every {} steps from step {}", period, start)
+        }
+        Outcome::StepLimit { steps, .. } => format!("No stable layout after {} steps", steps),
+    }
+}
+
+/// How a seating simulation ended, counting the initial layout as step 0
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    Stable { step: usize, layout: Layout },
+    Cycle { start: usize, period: usize, layout: Layout },
+    StepLimit { steps: usize, layout: Layout },
+}
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum Place {
+/// Which seats count as neighbours
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Neighbourhood {
+    Adjacent,
+    LineOfSight { max_distance: Option<usize> },
+    /// The places at these (row, column) offsets
+    Custom(Vec<(i32, i32)>),
+}
+
+const DIRECTIONS: [(i32, i32); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+/// The rules of the seating simulation, with thresholds counted in occupied neighbours
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeatingRules {
+    pub neighbourhood: Neighbourhood,
+    pub leave_threshold: usize,
+    pub occupy_threshold: usize,
+    pub floor_blocks_sight: bool,
+}
+
+impl SeatingRules {
+    /// The rules from part one
+    pub fn adjacent() -> Self {
+        Self { neighbourhood: Neighbourhood::Adjacent, leave_threshold: 4, occupy_threshold: 0, floor_blocks_sight: false }
+    }
+
+    /// The rules from part two
+    pub fn visible() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::LineOfSight { max_distance: None },
+            leave_threshold: 5,
+            occupy_threshold: 0,
+            floor_blocks_sight: false,
+        }
+    }
+
+    /// Builds rules from command-line options, starting from the part one rules
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut rules = Self::adjacent();
+        let mut args = args.iter().map(String::as_str).peekable();
+        let number = |s: Option<&str>, option: &str| {
+            s.and_then(|s| s.parse::<usize>().ok()).ok_or_else(|| format!("{} needs a number", option))
+        };
+        while let Some(arg) = args.next() {
+            match arg {
+                "--adjacent" => rules.neighbourhood = Neighbourhood::Adjacent,
+                "--sight" => {
+                    let max_distance = args.peek().and_then(|s| s.parse::<usize>().ok());
+                    if max_distance.is_some() {
+                        args.next();
+                    }
+                    rules.neighbourhood = Neighbourhood::LineOfSight { max_distance };
+                }
+                "--offsets" => {
+                    let offsets = args.next()
+                        .ok_or("--offsets needs a list of offsets")?
+                        .split(';')
+                        .map(|pair| {
+                            let (r, c) = pair.split_once(',')?;
+                            Some((r.trim().parse().ok()?, c.trim().parse().ok()?))
+                        })
+                        .collect::<Option<Vec<(i32, i32)>>>()
+                        .ok_or("Offsets look like 0,1;1,0;-1,-1")?;
+                    rules.neighbourhood = Neighbourhood::Custom(offsets);
+                }
+                "--leave" => rules.leave_threshold = number(args.next(), arg)?,
+                "--occupy" => rules.occupy_threshold = number(args.next(), arg)?,
+                "--floor-blocks" => rules.floor_blocks_sight = true,
+                _ => return Err(format!("Unknown option '{}'", arg)),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Place {
     Floor,
     Chair(bool),
 }
 
 impl Place {
-    pub fn from_str(s: &str) -> Vec<Place> {
+    /// Reads a row of the layout, e.g. `L.LL#`
+    pub fn parse_row(s: &str) -> Vec<Place> {
         s.chars()
             .map(|c| match c {
                 '.' => Place::Floor,
@@ -48,8 +178,8 @@ impl Display for Place {
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
-struct Layout {
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Layout {
     rows: Vec<Vec<Place>>,
 }
 
@@ -62,40 +192,34 @@ impl Layout {
         self.get(row, col).map(|p| p.is_occupied()).unwrap_or(false)
     }
 
-    pub fn count_visible(&self, row: usize, col: usize) -> usize {
-        self.lookup(row, col, -1, -1)
-            + self.lookup(row, col, -1, 0)
-            + self.lookup(row, col, -1, 1)
-            + self.lookup(row, col, 0, -1)
-            + self.lookup(row, col, 0, 1)
-            + self.lookup(row, col, 1, -1)
-            + self.lookup(row, col, 1, 0)
-            + self.lookup(row, col, 1, 1)
-    }
-
-    pub fn count_adjacent(&self, row: usize, col: usize) -> usize {
-        let mut result = 0;
-        result += if row > 0 && self.is_occupied(row - 1, col) { 1 } else { 0 };
-        result += if row > 0 && col > 0 && self.is_occupied(row - 1, col - 1) { 1 } else { 0 };
-        result += if row > 0 && self.is_occupied(row - 1, col + 1) { 1 } else { 0 };
-        result += if self.is_occupied(row + 1, col) { 1 } else { 0 };
-        result += if self.is_occupied(row + 1, col + 1) { 1 } else { 0 };
-        result += if col > 0 && self.is_occupied(row, col - 1) { 1 } else { 0 };
-        result += if col > 0 && self.is_occupied(row + 1, col - 1) { 1 } else { 0 };
-        result += if self.is_occupied(row, col + 1) { 1 } else { 0 };
-        result
+    /// The number of occupied neighbours of the seat at `row`, `col`
+    pub fn count_neighbours(&self, row: usize, col: usize, rules: &SeatingRules) -> usize {
+        match &rules.neighbourhood {
+            Neighbourhood::Adjacent => DIRECTIONS.iter()
+                .filter(|&&(dr, dc)| self.look(row, col, dr, dc, Some(1), rules.floor_blocks_sight))
+                .count(),
+            Neighbourhood::LineOfSight { max_distance } => DIRECTIONS.iter()
+                .filter(|&&(dr, dc)| self.look(row, col, dr, dc, *max_distance, rules.floor_blocks_sight))
+                .count(),
+            Neighbourhood::Custom(offsets) => offsets.iter()
+                .filter(|&&(dr, dc)| self.look(row, col, dr, dc, Some(1), true))
+                .count(),
+        }
     }
 
-    pub fn simulate_one(&self, count_fn: CountFn, max_occupied: usize) -> Self {
+    pub fn simulate_one(&self, rules: &SeatingRules) -> Self {
         let mut result = self.clone();
         self.rows.iter().enumerate().for_each(|(row, seats)| {
             seats.iter().enumerate().for_each(|(col, p)| {
-                let filled = count_fn(self, row, col);
+                if *p == Place::Floor {
+                    return;
+                }
+                let filled = self.count_neighbours(row, col, rules);
                 let occupied = self.is_occupied(row, col);
-                if occupied && filled >= max_occupied {
+                if occupied && filled >= rules.leave_threshold {
                     result.rows[row][col] = Place::Chair(false);
                 }
-                if !occupied && filled == 0 && *p != Place::Floor {
+                if !occupied && filled <= rules.occupy_threshold {
                     result.rows[row][col] = Place::Chair(true);
                 }
             })
@@ -103,16 +227,48 @@ impl Layout {
         result
     }
 
-    pub fn simulate(&self, count_fn: CountFn, max_occupied: usize) -> Layout {
+    /// Runs the simulation until a layout repeats, or for at most `max_steps` steps
+    pub fn simulate(&self, rules: &SeatingRules, max_steps: usize) -> Outcome {
+        self.simulate_with(rules, max_steps, |_| {})
+    }
+
+    /// Like `simulate`, but calls `on_generation` with each new layout, starting with this one
+    pub fn simulate_with<F: FnMut(&Layout)>(&self, rules: &SeatingRules, max_steps: usize, mut on_generation: F)
+                                            -> Outcome {
+        // Only the hash of each generation is kept, and a matching hash is confirmed against the real layout
+        let mut seen = HashMap::new();
         let mut last = self.clone();
-        loop {
-            let next = last.simulate_one(count_fn, max_occupied);
-            println!("{}\n\n\n", next);
-            if next == last {
-                return next;
+        on_generation(&last);
+        seen.insert(last.fingerprint(), 0);
+        for step in 1..=max_steps {
+            let next = last.simulate_one(rules);
+            let hash = next.fingerprint();
+            if let Some(&start) = seen.get(&hash) {
+                let repeated = if start == step - 1 { last == next } else { self.generation(rules, start) == next };
+                if repeated {
+                    let period = step - start;
+                    return if period == 1 {
+                        Outcome::Stable { step: start, layout: next }
+                    } else {
+                        Outcome::Cycle { start, period, layout: next }
+                    };
+                }
             }
+            on_generation(&next);
+            seen.insert(hash, step);
             last = next;
         }
+        Outcome::StepLimit { steps: max_steps, layout: last }
+    }
+
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn generation(&self, rules: &SeatingRules, steps: usize) -> Layout {
+        (0..steps).fold(self.clone(), |layout, _| layout.simulate_one(rules))
     }
 
     pub fn count_occupied(&self) -> usize {
@@ -121,26 +277,44 @@ impl Layout {
         })
     }
 
-    fn lookup(&self, row: usize, col: usize, dir_x: i32, dir_y: i32) -> usize {
-        let mut cur_row = row;
-        let mut cur_col = col;
+    fn look(&self, row: usize, col: usize, dir_row: i32, dir_col: i32, max_distance: Option<usize>,
+            floor_blocks: bool) -> bool {
+        let mut cur_row = row as i64;
+        let mut cur_col = col as i64;
+        let mut distance = 0;
         loop {
-            cur_row = if (cur_row as i32 + dir_y) < 0 {
-                return 0;
-            } else {
-                (cur_row as i32 + dir_y) as usize
-            };
-            cur_col = if (cur_col as i32 + dir_x) < 0 {
-                return 0;
-            } else {
-                (cur_col as i32 + dir_x) as usize
-            };
-            match self.get(cur_row, cur_col) {
-                Some(Place::Chair(true)) => return 1,
-                Some(Place::Chair(false)) => return 0,
-                Some(Place::Floor) => {}
-                None => return 0,
+            distance += 1;
+            if max_distance.is_some_and(|max| distance > max) {
+                return false;
+            }
+            cur_row += dir_row as i64;
+            cur_col += dir_col as i64;
+            if cur_row < 0 || cur_col < 0 {
+                return false;
             }
+            match self.get(cur_row as usize, cur_col as usize) {
+                Some(Place::Chair(occupied)) => return *occupied,
+                Some(Place::Floor) if !floor_blocks => {}
+                Some(Place::Floor) | None => return false,
+            }
+        }
+    }
+}
+
+impl Frame for Layout {
+    fn width(&self) -> usize {
+        self.rows.first().map(|r| r.len()).unwrap_or(0)
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Cell {
+        match self.get(y, x) {
+            Some(Place::Chair(true)) => Cell::Full,
+            Some(Place::Chair(false)) => Cell::Empty,
+            Some(Place::Floor) | None => Cell::Blank,
         }
     }
 }
@@ -162,7 +336,7 @@ fn read_data() -> Layout {
     let rows = values
         .lines()
         .filter(|s| !s.is_empty())
-        .map(|s| Place::from_str(s))
+        .map(Place::parse_row)
         .collect();
     Layout { rows }
 }
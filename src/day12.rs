@@ -1,19 +1,57 @@
+use std::fmt::{self, Display, Formatter};
 use std::fs;
 
 pub fn day12a() -> String {
     let mut nav = NavComputer::default();
     let ins = read_data();
     let pos = nav.exec(&ins);
-    println!("{}, {}", pos.0, pos.1);
-    format!("{:?}", nav.travelled())
+    println!("{}, {}", number(pos.0), number(pos.1));
+    number(nav.travelled())
 }
 
 pub fn day12b() -> String {
     let mut nav = NavComputer::new();
     let ins = read_data();
     let pos = nav.exec_wp(&ins);
-    println!("{}, {}", pos.0, pos.1);
-    format!("{:?}", nav.travelled())
+    println!("{}, {}", number(pos.0), number(pos.1));
+    number(nav.travelled())
+}
+
+const ROUTE_USAGE: &str = "Usage: day12route [--waypoint] [--svg <path>] [file]";
+
+/// Sails a route, the puzzle input by default, and describes it, optionally drawing it to an SVG file
+pub fn day12route(args: &[String]) -> String {
+    let mut waypoint = false;
+    let mut svg = None;
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--waypoint" => waypoint = true,
+            "--svg" => match args.next() {
+                Some(path) => svg = Some(path),
+                None => return ROUTE_USAGE.to_string(),
+            },
+            s if s.starts_with("--") => return ROUTE_USAGE.to_string(),
+            s => file = Some(s),
+        }
+    }
+    let ins = match read_file(file.unwrap_or("assets/day12.txt")) {
+        Ok(ins) => ins,
+        Err(e) => return e,
+    };
+    let mut nav = if waypoint { NavComputer::new() } else { NavComputer::default() };
+    if waypoint {
+        nav.exec_wp(&ins);
+    } else {
+        nav.exec(&ins);
+    }
+    if let Some(path) = svg {
+        if let Err(e) = fs::write(path, nav.to_svg()) {
+            return format!("Could not write {}: {}", path, e);
+        }
+    }
+    nav.summary().to_string()
 }
 
 fn read_data() -> Vec<Instruction> {
@@ -25,28 +63,100 @@ fn read_data() -> Vec<Instruction> {
         .collect()
 }
 
+fn read_file(file: &str) -> Result<Vec<Instruction>, String> {
+    let values = fs::read_to_string(file).map_err(|e| format!("Could not load {}: {}", file, e))?;
+    values
+        .lines()
+        .filter(|s| !s.is_empty())
+        .map(|s| Instruction::parse(s).ok_or_else(|| format!("Invalid instruction {:?}", s)))
+        .collect()
+}
+
+/// Formats whole numbers without a fractional part, so that right-angle routes read as they always have
+fn number(value: f64) -> String {
+    if (value - value.round()).abs() < 1e-9 {
+        format!("{}", value.round() as i64)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+/// A position as (east, north)
+pub type Point = (f64, f64);
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// The cosine and sine of an angle in degrees, exact for right angles so that turns don't accumulate errors
+fn cos_sin(degrees: f64) -> (f64, f64) {
+    let d = degrees.rem_euclid(360.0);
+    if d == 0.0 {
+        (1.0, 0.0)
+    } else if d == 90.0 {
+        (0.0, 1.0)
+    } else if d == 180.0 {
+        (-1.0, 0.0)
+    } else if d == 270.0 {
+        (0.0, -1.0)
+    } else {
+        let r = d.to_radians();
+        (r.cos(), r.sin())
+    }
+}
+
 #[derive(Default)]
-struct NavComputer {
-    origin: (i32, i32),
-    position: (i32, i32),
-    waypoint: (i32, i32),
-    facing: i32
+pub struct NavComputer {
+    origin: Point,
+    position: Point,
+    /// The waypoint, relative to the ship
+    waypoint: Point,
+    /// Degrees clockwise from east
+    heading: f64,
+    /// Every position of the ship, starting at the origin
+    track: Vec<Point>,
+    /// The absolute position of the waypoint alongside each entry of `track`
+    waypoints: Vec<Point>,
 }
 
 impl NavComputer {
     pub fn new() -> Self {
         Self {
-            waypoint: (10, 1),
+            waypoint: (10.0, 1.0),
             .. Self::default()
         }
     }
 
-    pub fn travelled(&self) -> i32 {
+    pub fn travelled(&self) -> f64 {
         (self.origin.0 - self.position.0).abs() + (self.origin.1 - self.position.1).abs()
     }
 
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn track(&self) -> &[Point] {
+        &self.track
+    }
+
+    pub fn waypoints(&self) -> &[Point] {
+        &self.waypoints
+    }
+
+    fn record(&mut self) {
+        self.track.push(self.position);
+        self.waypoints.push((self.position.0 + self.waypoint.0, self.position.1 + self.waypoint.1));
+    }
+
+    /// Turns the waypoint anticlockwise about the ship
+    fn rotate_waypoint(&mut self, degrees: f64) {
+        let (cos, sin) = cos_sin(degrees);
+        let (x, y) = self.waypoint;
+        self.waypoint = (x * cos - y * sin, x * sin + y * cos);
+    }
+
     pub fn mov_wp(&mut self, inst: &Instruction) {
-        match inst {
+        match *inst {
             Instruction::North(val) => {
                 self.waypoint.1 += val;
             }
@@ -60,22 +170,10 @@ impl NavComputer {
                 self.waypoint.0 -= val;
             }
             Instruction::Left(val) => {
-                self.waypoint = match val {
-                    0 | 360 => self.waypoint,
-                    90 => (-self.waypoint.1, self.waypoint.0),
-                    180 => (-self.waypoint.0, -self.waypoint.1),
-                    270 => (self.waypoint.1, -self.waypoint.0),
-                    _ => panic!(format!("Invalid left {}", val))
-                }
+                self.rotate_waypoint(val);
             }
             Instruction::Right(val) => {
-                self.waypoint = match val {
-                    0 | 360 => self.waypoint,
-                    90 => (self.waypoint.1, -self.waypoint.0),
-                    180 => (-self.waypoint.0, -self.waypoint.1),
-                    270 => (-self.waypoint.1, self.waypoint.0),
-                    _ => panic!(format!("Invalid right {}", val))
-                }
+                self.rotate_waypoint(-val);
             }
             Instruction::Fwd(val) => {
                 self.position.0 += self.waypoint.0 * val;
@@ -85,75 +183,216 @@ impl NavComputer {
     }
 
     pub fn mov(&mut self, inst: &Instruction) {
-        match inst {
+        match *inst {
             Instruction::North(val) => {
                 self.position.1 += val;
             }
-                Instruction::South(val) => {
-                    self.position.1 -= val;
-                }
+            Instruction::South(val) => {
+                self.position.1 -= val;
+            }
             Instruction::East(val) => {
                 self.position.0 += val;
             }
-                Instruction::West(val) => {
-                    self.position.0 -= val;
-                }
+            Instruction::West(val) => {
+                self.position.0 -= val;
+            }
             Instruction::Left(val) => {
-                self.facing = (self.facing - val/90 + 4) % 4;
+                self.heading = (self.heading - val).rem_euclid(360.0);
+            }
+            Instruction::Right(val) => {
+                self.heading = (self.heading + val).rem_euclid(360.0);
             }
-                Instruction::Right(val) => {
-                    self.facing = (self.facing + val/90) % 4;
-                }
             Instruction::Fwd(val) => {
-                match self.facing {
-                    0 => self.mov(&Instruction::East(*val)),
-                    1 => self.mov(&Instruction::South(*val)),
-                    2 => self.mov(&Instruction::West(*val)),
-                    3 => self.mov(&Instruction::North(*val)),
-                    _ => {
-                        panic!("{:?}", self.facing);
-
-                    }
-                }
+                let (cos, sin) = cos_sin(self.heading);
+                self.position.0 += cos * val;
+                self.position.1 -= sin * val;
             }
         }
     }
 
-    pub fn exec(&mut self, inst: &[Instruction]) -> (i32, i32) {
-        inst.iter().for_each(|i| self.mov(i));
+    pub fn exec(&mut self, inst: &[Instruction]) -> Point {
+        if self.track.is_empty() {
+            self.record();
+        }
+        for i in inst {
+            self.mov(i);
+            self.record();
+        }
         self.position
     }
 
-    pub fn exec_wp(&mut self, inst: &[Instruction]) -> (i32, i32) {
-        inst.iter().for_each(|i| self.mov_wp(i));
+    pub fn exec_wp(&mut self, inst: &[Instruction]) -> Point {
+        if self.track.is_empty() {
+            self.record();
+        }
+        for i in inst {
+            self.mov_wp(i);
+            self.record();
+        }
         self.position
     }
+
+    pub fn summary(&self) -> RouteSummary {
+        let start = self.track.first().copied().unwrap_or(self.origin);
+        let mut summary = RouteSummary {
+            manhattan: self.travelled(),
+            distance: 0.0,
+            min: start,
+            max: start,
+            furthest: start,
+            furthest_distance: distance(self.origin, start),
+        };
+        for (i, &p) in self.track.iter().enumerate() {
+            if i > 0 {
+                summary.distance += distance(self.track[i - 1], p);
+            }
+            summary.min = (summary.min.0.min(p.0), summary.min.1.min(p.1));
+            summary.max = (summary.max.0.max(p.0), summary.max.1.max(p.1));
+            let from_origin = distance(self.origin, p);
+            if from_origin > summary.furthest_distance {
+                summary.furthest = p;
+                summary.furthest_distance = from_origin;
+            }
+        }
+        summary
+    }
+
+    /// Draws the recorded route as a standalone SVG image, with north up
+    pub fn to_svg(&self) -> String {
+        const SIZE: f64 = 800.0;
+        const MARGIN: f64 = 40.0;
+        let show_waypoints = self.waypoints != self.track;
+        let mut points = vec![self.origin];
+        points.extend_from_slice(&self.track);
+        if show_waypoints {
+            points.extend_from_slice(&self.waypoints);
+        }
+        let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+        let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+        let extent = (max_x - min_x).max(max_y - min_y).max(1.0);
+        let scale = (SIZE - 2.0 * MARGIN) / extent;
+        let to_svg = |p: Point| (MARGIN + (p.0 - min_x) * scale, MARGIN + (max_y - p.1) * scale);
+        let polyline = |track: &[Point]| {
+            track.iter()
+                .map(|&p| {
+                    let (x, y) = to_svg(p);
+                    format!("{:.2},{:.2}", x, y)
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        let mut svg = Vec::new();
+        svg.push(format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">",
+            SIZE
+        ));
+        svg.push(format!("<rect width=\"{0}\" height=\"{0}\" fill=\"white\"/>", SIZE));
+
+        // Compass axes through the origin
+        let (ox, oy) = to_svg(self.origin);
+        svg.push("<g stroke=\"#bbb\" stroke-dasharray=\"4 4\">".to_string());
+        svg.push(format!("<line x1=\"0\" y1=\"{1:.2}\" x2=\"{0}\" y2=\"{1:.2}\"/>", SIZE, oy));
+        svg.push(format!("<line x1=\"{1:.2}\" y1=\"0\" x2=\"{1:.2}\" y2=\"{0}\"/>", SIZE, ox));
+        svg.push("</g>".to_string());
+        svg.push("<g fill=\"#888\" font-family=\"sans-serif\" font-size=\"14\" text-anchor=\"middle\">".to_string());
+        svg.push(format!("<text x=\"{:.2}\" y=\"16\">N</text>", ox));
+        svg.push(format!("<text x=\"{:.2}\" y=\"{}\">S</text>", ox, SIZE - 6.0));
+        svg.push(format!("<text x=\"{}\" y=\"{:.2}\">E</text>", SIZE - 10.0, oy - 6.0));
+        svg.push(format!("<text x=\"10\" y=\"{:.2}\">W</text>", oy - 6.0));
+        svg.push("</g>".to_string());
+
+        if show_waypoints {
+            svg.push(format!(
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#7ab\" stroke-width=\"1\" stroke-opacity=\"0.6\"/>",
+                polyline(&self.waypoints)
+            ));
+            svg.push("<g fill=\"#37a\">".to_string());
+            for &p in &self.waypoints {
+                let (x, y) = to_svg(p);
+                svg.push(format!("<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"2\"/>", x, y));
+            }
+            svg.push("</g>".to_string());
+        }
+        svg.push(format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#c33\" stroke-width=\"2\" stroke-linejoin=\"round\"/>",
+            polyline(&self.track)
+        ));
+
+        if let (Some(&start), Some(&end)) = (self.track.first(), self.track.last()) {
+            let (sx, sy) = to_svg(start);
+            let (ex, ey) = to_svg(end);
+            svg.push(format!(
+                "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"6\" fill=\"#2a2\"><title>Start</title></circle>",
+                sx, sy
+            ));
+            svg.push(format!(
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"12\" height=\"12\" fill=\"#222\"><title>End ({}, {})</title></rect>",
+                ex - 6.0, ey - 6.0, number(end.0), number(end.1)
+            ));
+        }
+        svg.push("</svg>".to_string());
+        svg.join("\n") + "\n"
+    }
 }
 
-#[derive(Clone)]
-enum Instruction {
-    North(i32),
-    South(i32),
-    East(i32),
-    West(i32),
-    Left(i32),
-    Right(i32),
-    Fwd(i32),
+/// Statistics about the recorded track of the ship
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSummary {
+    /// Manhattan distance from the origin to where the ship ended up
+    pub manhattan: f64,
+    /// The length of the track, in straight lines between positions
+    pub distance: f64,
+    /// South-west corner of the bounding box
+    pub min: Point,
+    /// North-east corner of the bounding box
+    pub max: Point,
+    /// The point on the track furthest from the origin, in a straight line
+    pub furthest: Point,
+    pub furthest_distance: f64,
+}
+
+impl Display for RouteSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Manhattan distance: {}", number(self.manhattan))?;
+        writeln!(f, "Distance travelled: {}", number(self.distance))?;
+        writeln!(f, "Bounding box: ({}, {}) to ({}, {})",
+                 number(self.min.0), number(self.min.1), number(self.max.0), number(self.max.1))?;
+        write!(f, "Furthest point: ({}, {}), {} from the origin",
+               number(self.furthest.0), number(self.furthest.1), number(self.furthest_distance))
+    }
+}
+
+/// A navigation instruction. Distances and angles in degrees may be fractional.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    North(f64),
+    South(f64),
+    East(f64),
+    West(f64),
+    Left(f64),
+    Right(f64),
+    Fwd(f64),
 }
 
 impl Instruction {
     fn from_str(s: &str) -> Self {
-        let ins = s.as_bytes()[0] as char;
-        let val = s[1..].parse::<i32>().unwrap();
+        Self::parse(s).expect("Unknown instruction")
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut chars = s.trim().chars();
+        let ins = chars.next()?;
+        let val = chars.as_str().parse::<f64>().ok().filter(|v| v.is_finite())?;
         match ins {
-            'N'  => Instruction::North(val),
-            'S'  => Instruction::South(val),
-            'E'  => Instruction::East(val),
-            'W'  => Instruction::West(val),
-            'L'  => Instruction::Left(val),
-            'R'  => Instruction::Right(val),
-            'F'  => Instruction::Fwd(val),
-            _ => panic!("Unknown instruction"),
+            'N'  => Some(Instruction::North(val)),
+            'S'  => Some(Instruction::South(val)),
+            'E'  => Some(Instruction::East(val)),
+            'W'  => Some(Instruction::West(val)),
+            'L'  => Some(Instruction::Left(val)),
+            'R'  => Some(Instruction::Right(val)),
+            'F'  => Some(Instruction::Fwd(val)),
+            _ => None,
         }
     }
-}
\ No newline at end of file
+}
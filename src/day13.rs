@@ -1,80 +1,158 @@
+use crate::number_theory::{crt, CrtError};
 use std::fs;
 
 pub fn day13a() -> String {
-    let (ts, buses) = read_data();
-    let mut time = ts;
-    loop {
-        if let Some(id) = buses.iter().find(|&&id| time % id == 0) {
-            return format!("{}", id * (time - ts));
-        }
-        time += 1
+    let (ts, schedule) = read_data();
+    match schedule.next_departures(ts).first() {
+        Some(&(id, time)) => format!("{}", id * (time - ts)),
+        None => "No buses".to_string(),
     }
 }
 
 pub fn day13b() -> String {
-    let mut vals = read_data2();
-    vals.sort_by(|a, b| b.1.cmp(&a.1));
-    let mods = vals.iter().map(|(_, p)| *p as i64).collect::<Vec<i64>>();
-    let resids = vals.iter().map(|(i, p)| *p as i64 - *i as i64).collect::<Vec<i64>>();
+    let (_, schedule) = read_data();
+    match Schedule::alignment(&schedule.offsets()) {
+        Ok(alignment) => alignment.first.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+const SCHEDULE_USAGE: &str = "Usage: day13schedule [time] [--buses <id@offset,...>] [--count <n>]";
+
+/// Lists the next departure of every bus, and with `--buses 17@0,13@2,...` the next times those buses line up
+pub fn day13schedule(args: &[String]) -> String {
+    let (ts, schedule) = read_data();
+    let mut time = ts;
+    let mut buses = None;
+    let mut count = 1;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--buses" => match args.next().map(|s| parse_offsets(s)) {
+                Some(Ok(b)) => buses = Some(b),
+                Some(Err(e)) => return e,
+                None => return SCHEDULE_USAGE.to_string(),
+            },
+            "--count" => match args.next().and_then(|s| s.parse::<usize>().ok()) {
+                Some(n) => count = n,
+                None => return SCHEDULE_USAGE.to_string(),
+            },
+            s => match s.parse::<u64>() {
+                Ok(t) => time = t,
+                Err(_) => return SCHEDULE_USAGE.to_string(),
+            },
+        }
+    }
 
-    chinese_remainder(&resids, &mods).unwrap().to_string()
+    let mut lines = schedule.next_departures(time)
+        .iter()
+        .map(|(id, t)| format!("Bus {} departs at {}, {} from now", id, t, t - time))
+        .collect::<Vec<String>>();
+    if let Some(buses) = buses {
+        match Schedule::alignment(&buses) {
+            Ok(alignment) => {
+                lines.push(format!("The buses line up every {}:", alignment.period));
+                lines.extend(alignment.times_from(time as u128).take(count).map(|t| t.to_string()));
+            }
+            Err(e) => lines.push(e.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+/// Parses `id@offset` pairs separated by commas
+fn parse_offsets(s: &str) -> Result<Vec<(u64, u64)>, String> {
+    s.split(',')
+        .map(|pair| {
+            let mut parts = pair.split('@');
+            let id = parts.next().and_then(|id| id.parse::<u64>().ok()).filter(|&id| id > 0);
+            let offset = parts.next().map(|o| o.parse::<u64>().ok()).unwrap_or(Some(0));
+            match (id, offset, parts.next()) {
+                (Some(id), Some(offset), None) => Ok((id, offset)),
+                _ => Err(format!("Invalid bus {:?}, expected id@offset", pair)),
+            }
+        })
+        .collect()
 }
 
-fn read_data() -> (usize, Vec<usize>) {
+fn read_data() -> (u64, Schedule) {
     let values = fs::read_to_string("assets/day13.txt").expect("Could not load file");
     let lines = values
         .lines()
         .filter(|s| !s.is_empty())
-        .map(String::from)
-        .collect::<Vec<String>>();
-    let ts = lines[0].parse::<usize>().unwrap();
-    let mut busses = lines[1].split(',')
-        .filter_map(|s| s.parse::<usize>().ok())
-        .collect::<Vec<usize>>();
-    busses.sort_unstable();
-    (ts, busses)
+        .collect::<Vec<&str>>();
+    let ts = lines[0].parse::<u64>().unwrap();
+    (ts, Schedule::parse(lines[1]))
 }
 
-// from: https://rosettacode.org/wiki/Chinese_remainder_theorem#Rust
-#[allow(clippy::many_single_char_names)]
-fn egcd(a: i64, b: i64) -> (i64, i64, i64) {
-    if a == 0 {
-        (b, 0, 1)
-    } else {
-        let (g, x, y) = egcd(b % a, a);
-        (g, y - (b / a) * x, x)
-    }
+/// The buses in service, by their position in the timetable. Out of service buses are `None`.
+pub struct Schedule {
+    buses: Vec<Option<u64>>,
 }
 
-fn mod_inv(x: i64, n: i64) -> Option<i64> {
-    let (g, x, _) = egcd(x, n);
-    if g == 1 {
-        Some((x % n + n) % n)
-    } else {
-        None
+impl Schedule {
+    pub fn parse(line: &str) -> Self {
+        Self {
+            buses: line.split(',').map(|s| s.trim().parse::<u64>().ok().filter(|&id| id > 0)).collect(),
+        }
     }
-}
 
-fn chinese_remainder(residues: &[i64], modulii: &[i64]) -> Option<i64> {
-    let prod = modulii.iter().product::<i64>();
-    let mut sum = 0;
-    for (&residue, &modulus) in residues.iter().zip(modulii) {
-        let p = prod / modulus;
-        sum += residue * mod_inv(p, modulus)? * p
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.buses.iter().flatten().copied()
+    }
+
+    /// Each bus in service paired with its position in the timetable, which is the contest's offset
+    pub fn offsets(&self) -> Vec<(u64, u64)> {
+        self.buses.iter()
+            .enumerate()
+            .filter_map(|(i, id)| id.map(|id| (id, i as u64)))
+            .collect()
+    }
+
+    /// The first time at or after `time` that bus `id` departs
+    pub fn next_departure(id: u64, time: u64) -> u64 {
+        time.div_ceil(id) * id
+    }
+
+    /// The next departure of every bus at or after `time`, soonest first
+    pub fn next_departures(&self, time: u64) -> Vec<(u64, u64)> {
+        let mut departures = self.ids()
+            .map(|id| (id, Self::next_departure(id, time)))
+            .collect::<Vec<(u64, u64)>>();
+        departures.sort_by_key(|&(id, t)| (t, id));
+        departures
+    }
+
+    /// When each bus `id` departs `offset` after the same time, for `(id, offset)` pairs
+    pub fn alignment(buses: &[(u64, u64)]) -> Result<Alignment, CrtError> {
+        let congruences = buses.iter()
+            .map(|&(id, offset)| (-(offset as i128), id as i128))
+            .collect::<Vec<(i128, i128)>>();
+        let (first, period) = crt(&congruences)?;
+        Ok(Alignment { first: first as u128, period: period as u128 })
     }
-    Some(sum % prod)
 }
 
-fn read_data2() -> Vec<(usize, usize)> {
-    let values = fs::read_to_string("assets/day13.txt").expect("Could not load file");
-    let lines = values
-        .split('\n')
-        .filter(|s| !s.is_empty())
-        .map(String::from)
-        .collect::<Vec<String>>();
-    lines[1]
-        .split(',')
-        .enumerate()
-        .filter_map(|(i, s)| s.parse::<usize>().ok().map(|v| (i, v)))
-        .collect::<Vec<(usize, usize)>>()
+/// The times at which a set of buses line up: `first`, and every `period` after it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alignment {
+    pub first: u128,
+    pub period: u128,
+}
+
+impl Alignment {
+    /// The first time at or after `time` that the buses line up
+    pub fn next_from(&self, time: u128) -> Option<u128> {
+        if time <= self.first {
+            return Some(self.first);
+        }
+        let periods = (time - self.first).div_ceil(self.period);
+        periods.checked_mul(self.period)?.checked_add(self.first)
+    }
+
+    /// Every time at or after `time` that the buses line up
+    pub fn times_from(&self, time: u128) -> impl Iterator<Item = u128> {
+        let period = self.period;
+        std::iter::successors(self.next_from(time), move |t| t.checked_add(period))
+    }
 }
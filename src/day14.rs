@@ -1,26 +1,41 @@
 use std::collections::HashMap;
 use regex::Regex;
-use crate::bits::{bit_array, assign_bits, set_bit, clear_bit, read_data};
+use crate::bits::{set_bit, clear_bit, read_data};
 
 pub fn day14a() -> String {
     let lines = read_data("assets/day14.txt");
     let mut machine = Machine::read_program(lines);
     machine.exec();
-    let res = machine.mem.values().into_iter().sum::<usize>();
-    format!("{}", res)
+    format!("{}", machine.mem.sum())
 }
 
 pub fn day14b() -> String {
     let lines = read_data("assets/day14.txt");
     let mut machine = Machine::read_program(lines);
     machine.exec2();
-    let res = machine.mem.values().into_iter().sum::<usize>();
-    format!("{}", res)
+    format!("{}", machine.mem.sum())
 }
 
 struct Machine {
     instructions: Vec<Instruction>,
-    mem: HashMap<usize, usize>,
+    mem: Memory,
+}
+
+/// What a program wrote, in the form the decoder chip that ran it keeps it
+enum Memory {
+    /// Version 1: one value per address
+    Values(HashMap<usize, usize>),
+    /// Version 2: values written to floating address patterns
+    Floating(FloatingMemory),
+}
+
+impl Memory {
+    fn sum(&self) -> u128 {
+        match self {
+            Memory::Values(values) => values.values().map(|&v| v as u128).sum(),
+            Memory::Floating(floating) => floating.sum(),
+        }
+    }
 }
 
 impl Machine {
@@ -40,57 +55,122 @@ impl Machine {
         }
         Self {
             instructions,
-            mem: HashMap::new(),
+            mem: Memory::Values(HashMap::new()),
         }
     }
 
     pub fn exec(&mut self) {
-        self.mem.clear();
+        let mut mem = HashMap::new();
         let mut mask = [BitMap::Nop; 36];
         for ins in &self.instructions {
             match ins {
                 Instruction::Mask(m) => mask.copy_from_slice(m),
                 Instruction::Mem((index, val)) => {
                     let v = apply_mask(*val, &mask);
-                    let _ = self.mem.insert(*index, v);
+                    let _ = mem.insert(*index, v);
                 }
             }
         }
+        self.mem = Memory::Values(mem);
     }
 
     pub fn exec2(&mut self) {
-        let mut mem = HashMap::new();
+        let mut floating = FloatingMemory::default();
         let mut mask = [BitMap::Nop; 36];
         for ins in &self.instructions {
             match ins {
                 Instruction::Mask(m) => mask.copy_from_slice(m),
                 Instruction::Mem((index, val)) => {
-                    let addresses = apply_mask_address(*index, &mask);
-                    addresses.into_iter().for_each(|addr| { mem.insert(addr, *val); })
+                    floating.write(AddressPattern::from_mask(*index, &mask), *val);
                 }
             }
         }
-        self.mem = mem;
+        self.mem = Memory::Floating(floating);
     }
 }
 
-fn apply_mask_address(index: usize, mask: &[BitMap; 36]) -> Vec<usize> {
-    let base = apply_mask2(index, mask);
-    let mut result = Vec::new();
-    // A vec of bit values 2, 8, 16 etc
-    let floating_indices = mask.iter()
-        .enumerate()
-        .filter(|(_, &b)| matches!(b, BitMap::Nop))
-        .map(|(i, _)| 35 - i)
-        .collect::<Vec<usize>>();
-    // Construct bitmask
-    for combo_val in 0..(1 << floating_indices.len()) {
-        let arr = bit_array(combo_val);
-        let iter = floating_indices.iter().copied()
-            .zip(arr.iter().copied());
-        result.push(assign_bits(base, iter));
+/// The addresses whose `floating` bits take any value and whose other bits match `fixed`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressPattern {
+    fixed: usize,
+    floating: usize,
+}
+
+impl AddressPattern {
+    pub fn new(fixed: usize, floating: usize) -> Self {
+        Self { fixed: fixed & !floating, floating }
+    }
+
+    /// The addresses written through a version 2 decoder mask
+    fn from_mask(index: usize, mask: &[BitMap; 36]) -> Self {
+        let floating = mask.iter()
+            .enumerate()
+            .filter(|(_, &b)| matches!(b, BitMap::Nop))
+            .fold(0, |f, (i, _)| set_bit(f, 35 - i));
+        Self::new(apply_mask2(index, mask), floating)
+    }
+
+    /// The number of addresses in the pattern
+    pub fn count(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        (address & !self.floating) == self.fixed
+    }
+
+    /// Whether any address is in both patterns, i.e. they agree on every bit that neither lets float
+    pub fn overlaps(&self, other: &Self) -> bool {
+        (self.fixed ^ other.fixed) & !(self.floating | other.floating) == 0
+    }
+
+    /// The addresses in `self` but not in `other`, as disjoint patterns
+    pub fn subtract(&self, other: &Self) -> Vec<Self> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        // Each piece matches `other` on the bits before one that floats only in `self`, and differs at that bit
+        let mut pieces = Vec::new();
+        let mut rest = *self;
+        let mut bits = self.floating & !other.floating;
+        while bits != 0 {
+            let bit = bits & bits.wrapping_neg();
+            bits &= !bit;
+            rest.floating &= !bit;
+            pieces.push(Self::new(rest.fixed | (!other.fixed & bit), rest.floating));
+            rest.fixed |= other.fixed & bit;
+        }
+        pieces
+    }
+}
+
+/// Memory written through version 2 decoder masks, kept as disjoint address patterns
+#[derive(Default)]
+pub struct FloatingMemory {
+    regions: Vec<(AddressPattern, usize)>,
+}
+
+impl FloatingMemory {
+    /// Writes `value` to every address in `pattern`, carving it out of the regions written before
+    pub fn write(&mut self, pattern: AddressPattern, value: usize) {
+        let mut regions = Vec::with_capacity(self.regions.len() + 1);
+        for (region, v) in self.regions.drain(..) {
+            regions.extend(region.subtract(&pattern).into_iter().map(|r| (r, v)));
+        }
+        regions.push((pattern, value));
+        self.regions = regions;
+    }
+
+    pub fn get(&self, address: usize) -> usize {
+        self.regions.iter()
+            .find(|(r, _)| r.contains(address))
+            .map_or(0, |&(_, v)| v)
+    }
+
+    /// The sum of every value in memory, which can't overflow since there are at most 2^64 addresses
+    pub fn sum(&self) -> u128 {
+        self.regions.iter().map(|&(r, v)| r.count() * v as u128).sum()
     }
-    result
 }
 
 fn apply_mask(val: usize, mask: &[BitMap; 36]) -> usize {
@@ -148,3 +228,50 @@ impl BitMap {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const BITS: usize = 6;
+
+    fn random_pattern<R: Rng>(rng: &mut R) -> AddressPattern {
+        AddressPattern::new(rng.gen_range(0..1 << BITS), rng.gen_range(0..1 << BITS))
+    }
+
+    #[test]
+    fn subtract_leaves_disjoint_pieces() {
+        let mut rng = StdRng::seed_from_u64(14);
+        for _ in 0..500 {
+            let (a, b) = (random_pattern(&mut rng), random_pattern(&mut rng));
+            let pieces = a.subtract(&b);
+            for address in 0..1 << BITS {
+                let holders = pieces.iter().filter(|p| p.contains(address)).count();
+                let expected = a.contains(address) && !b.contains(address);
+                assert_eq!(holders, expected as usize, "{:?} - {:?} at {}", a, b, address);
+            }
+        }
+    }
+
+    #[test]
+    fn floating_memory_agrees_with_listed_addresses() {
+        let mut rng = StdRng::seed_from_u64(50);
+        for _ in 0..100 {
+            let mut memory = FloatingMemory::default();
+            let mut expected = HashMap::new();
+            for _ in 0..rng.gen_range(1..10) {
+                let pattern = random_pattern(&mut rng);
+                let value = rng.gen_range(0..1000);
+                memory.write(pattern, value);
+                (0..1 << BITS).filter(|&a| pattern.contains(a)).for_each(|a| {
+                    expected.insert(a, value);
+                });
+            }
+            for address in 0..1 << BITS {
+                assert_eq!(memory.get(address), expected.get(&address).copied().unwrap_or(0));
+            }
+            assert_eq!(memory.sum(), expected.values().map(|&v| v as u128).sum::<u128>());
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::bits::read_data;
+use crate::render::{Animator, Cell, Frame};
 
 pub fn day17a() -> String {
     let data = read_data("assets/day17.txt");
@@ -20,6 +21,64 @@ pub fn day17b() -> String {
     format!("{}", cube.count_active())
 }
 
+/// Animates six rounds of the cube as the x-y slice through `z` and `w`, taking the `Animator::from_args` options
+pub fn day17animate(args: &[String]) -> String {
+    let (mut animator, rest) = match Animator::from_args(args) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let (z, w) = match (rest.first().map(|s| s.parse::<isize>()), rest.get(1).map(|s| s.parse::<isize>())) {
+        (Some(Ok(z)), Some(Ok(w))) => (z, w),
+        _ => return "Usage: day17animate <z> <w> [animation options]".to_string(),
+    };
+    let data = read_data("assets/day17.txt");
+    let extent = data.iter().map(|s| s.len()).max().unwrap_or(0) as isize;
+    let mut cube = parse_data(data);
+    // The pattern can grow by one cell in each direction per round
+    let (min, max) = (-ROUNDS, extent + ROUNDS - 1);
+    for round in 0..=ROUNDS {
+        if let Err(e) = animator.draw(&CubeSlice { cube: &cube, z, w, min, max }) {
+            return format!("Could not draw frame: {}", e);
+        }
+        if round < ROUNDS {
+            cube = match cube.simulate() {
+                Some(c) => c,
+                None => return format!("Size {} is insufficient", cube.size),
+            };
+        }
+    }
+    format!("{} frames drawn. {} active", animator.frames(), cube.count_active())
+}
+
+const ROUNDS: isize = 6;
+
+/// The x-y plane of a cube at a fixed `z` and `w`, between `min` and `max` (inclusive) in both x and y
+struct CubeSlice<'a> {
+    cube: &'a Cube,
+    z: isize,
+    w: isize,
+    min: isize,
+    max: isize,
+}
+
+impl Frame for CubeSlice<'_> {
+    fn width(&self) -> usize {
+        (self.max - self.min + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        self.width()
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Cell {
+        let pos = (self.min + x as isize, self.min + y as isize, self.z, self.w);
+        match self.cube.is_active(Cube::coord_to_index(self.cube.size, pos)) {
+            Some(true) => Cell::Full,
+            _ => Cell::Blank,
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Cube {
     size: usize,
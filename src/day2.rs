@@ -1,31 +1,74 @@
 use std::fs;
 use regex::Regex;
+use itertools::Itertools;
 
 pub fn day2a() -> String {
-    let policies = read_data();
+    let policies = read_data("assets/passwords.txt");
     let total_valid = policies
         .iter()
-        .filter(|&p| p.is_valid_at_sled())
+        .filter(|&p| SledRule.check(p).is_ok())
         .count();
     total_valid.to_string()
 }
 
 pub fn day2b() -> String {
-    let policies = read_data();
+    let policies = read_data("assets/passwords.txt");
     let total_valid = policies
         .iter()
-        .filter(|&p| p.is_valid_at_tobbogan())
+        .filter(|&p| TobogganRule.check(p).is_ok())
         .count();
     total_valid.to_string()
 }
 
+/// Checks every password in a file against the named rules, or all of them, explaining each failure
+pub fn day2report(args: &[String]) -> String {
+    let file = match args.first() {
+        Some(f) => f.as_str(),
+        None => return format!("Usage: day2report <file> [{}]...", RULES.iter().map(|r| r.name()).join("|")),
+    };
+    let rules = if args.len() > 1 {
+        match args[1..].iter().map(|name| find_rule(name).ok_or(name)).collect::<Result<Vec<_>, _>>() {
+            Ok(rules) => rules,
+            Err(name) => return format!("Unknown rule '{}'", name),
+        }
+    } else {
+        RULES.to_vec()
+    };
+    let src = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return format!("Could not read {}: {}", file, e),
+    };
+    let regex = Regex::new(REGEX).unwrap();
+    let mut passed = vec![0; rules.len()];
+    for (i, line) in src.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let policy = match PasswordPolicy::new(line, &regex) {
+            Some(p) => p,
+            None => {
+                println!("Line {}: could not parse '{}'", i + 1, line);
+                continue;
+            }
+        };
+        for (rule, count) in rules.iter().zip(passed.iter_mut()) {
+            match rule.check(&policy) {
+                Ok(()) => *count += 1,
+                Err(reason) => println!("Line {}: '{}' fails {}: {}", i + 1, policy.password, rule.name(), reason),
+            }
+        }
+    }
+    rules.iter()
+        .zip(passed)
+        .map(|(rule, n)| format!("{}: {} valid", rule.name(), n))
+        .join("\n")
+}
+
 const REGEX: &str = r"^(\d+)-(\d+) (.): (.*)$";
 
+/// One line of the password file, whose numbers and letter mean different things to each rule
 #[derive(Debug)]
 pub struct PasswordPolicy {
     min: usize,
     max: usize,
-    letter: String,
+    letter: char,
     password: String,
 }
 
@@ -34,32 +77,78 @@ impl PasswordPolicy {
         let matches = re.captures(s)?;
         let min = matches.get(1).and_then(|s| s.as_str().parse::<usize>().ok())?;
         let max = matches.get(2).and_then(|s| s.as_str().parse::<usize>().ok())?;
-        let letter = matches.get(3)?.as_str().to_string();
+        let letter = matches.get(3)?.as_str().chars().next()?;
         let password = matches.get(4)?.as_str().to_string();
         Some(Self { min, max, letter, password })
     }
 
-    pub fn is_valid_at_sled(&self) -> bool {
-        // Count how many times letter is in password
-        let count = self.password.matches(&self.letter).count();
-        count >= self.min && count <= self.max
+    /// The character at 1-based `position`
+    fn char_at(&self, position: usize) -> Option<char> {
+        position.checked_sub(1).and_then(|i| self.password.chars().nth(i))
+    }
+}
+
+/// A way of interpreting a password policy
+pub trait PasswordRule {
+    fn name(&self) -> &'static str;
+    fn check(&self, policy: &PasswordPolicy) -> Result<(), String>;
+}
+
+/// The sled rental place: the letter must appear between `min` and `max` times
+#[derive(Clone, Copy)]
+pub struct SledRule;
+
+/// The Official Toboggan Corporate Policy: exactly one of positions `min` and `max` holds the letter
+#[derive(Clone, Copy)]
+pub struct TobogganRule;
+
+pub const RULES: [&dyn PasswordRule; 2] = [&SledRule, &TobogganRule];
+
+pub fn find_rule(name: &str) -> Option<&'static dyn PasswordRule> {
+    RULES.iter().copied().find(|r| r.name() == name)
+}
+
+impl PasswordRule for SledRule {
+    fn name(&self) -> &'static str {
+        "sled"
+    }
+
+    fn check(&self, policy: &PasswordPolicy) -> Result<(), String> {
+        let count = policy.password.chars().filter(|&c| c == policy.letter).count();
+        if count >= policy.min && count <= policy.max {
+            Ok(())
+        } else {
+            Err(format!("'{}' appears {} times, not {} to {}", policy.letter, count, policy.min, policy.max))
+        }
     }
+}
 
-    pub fn is_valid_at_tobbogan(&self) -> bool {
-        if self.password.len() < self.min.max(self.max) { return false; }
-        let letter = self.letter.as_bytes()[0] as char;
-        let chars = self.password.chars().collect::<Vec<char>>();
-        let first = chars[self.min - 1] == letter;
-        let second = chars[self.max - 1] == letter;
-        first ^ second
+impl PasswordRule for TobogganRule {
+    fn name(&self) -> &'static str {
+        "toboggan"
+    }
+
+    fn check(&self, policy: &PasswordPolicy) -> Result<(), String> {
+        let describe = |position: usize| match policy.char_at(position) {
+            Some(c) => format!("position {} is '{}'", position, c),
+            None => format!("there is no position {}", position),
+        };
+        let first = policy.char_at(policy.min) == Some(policy.letter);
+        let second = policy.char_at(policy.max) == Some(policy.letter);
+        match (first, second) {
+            (true, false) | (false, true) => Ok(()),
+            (true, true) => Err(format!("'{}' is at both positions {} and {}", policy.letter, policy.min, policy.max)),
+            (false, false) => Err(format!("'{}' is at neither position: {} and {}",
+                                          policy.letter, describe(policy.min), describe(policy.max))),
+        }
     }
 }
 
-fn read_data() -> Vec<PasswordPolicy> {
-    let values = fs::read_to_string("assets/passwords.txt").expect("Could not load file");
+fn read_data(file: &str) -> Vec<PasswordPolicy> {
+    let values = fs::read_to_string(file).expect("Could not load file");
     let regex = Regex::new(REGEX).unwrap();
     values
         .split('\n')
         .filter_map(|s| PasswordPolicy::new(s, &regex))
         .collect()
-}
\ No newline at end of file
+}
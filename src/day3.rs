@@ -1,52 +1,141 @@
+use std::fmt::{Display, Formatter};
 use std::fs;
+use itertools::Itertools;
 
-fn read_data() -> Vec<String> {
+fn read_data() -> TreeMap {
     let values = fs::read_to_string("assets/day3.txt").expect("Could not load file");
-    values
-        .split('\n')
-        .filter(|&s| !s.is_empty())
-        .map(String::from)
-        .collect()
+    TreeMap::new(&values)
 }
 
 pub fn day3a() -> String {
-    let rows = read_data();
-    let trees = track_path(&rows, 3, 1);
+    let map = read_data();
+    let trees = map.trees_on(Slope::new(3, 1));
     trees.to_string()
 }
 
 pub fn day3b() -> String {
-    let rows = read_data();
-    let trees = [
-        track_path(&rows, 1, 1),
-        track_path(&rows, 3, 1),
-        track_path(&rows, 5, 1),
-        track_path(&rows, 7, 1),
-        track_path(&rows, 1, 2),
-    ]
-        .iter().product::<usize>();
+    let map = read_data();
+    let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)].iter()
+        .map(|&(right, down)| Slope::new(right, down))
+        .collect::<Vec<Slope>>();
+    let trees = map.survey(&slopes)
+        .iter()
+        .map(|(_, n)| n)
+        .product::<usize>();
     trees.to_string()
 }
 
-fn track_path(rows: &[String], n_right: usize, n_down: usize) -> usize {
-    let (trees, _) = rows.iter()
-        .skip(n_down)
-        .step_by(n_down)
-        .fold((0usize, 0usize), |(tot, pos), s| {
-            let (new_pos, is_tree) = move_right(s.as_str(), pos, n_right);
-            let new_tot = if is_tree { tot + 1 } else { tot };
-            (new_tot, new_pos)
-        });
-    trees
+/// Counts the trees on each `right,down` slope or range of slopes, and with `--show` maps the best one
+pub fn day3survey(args: &[String]) -> String {
+    let show = args.iter().any(|a| a == "--show");
+    let slopes = args.iter()
+        .filter(|a| *a != "--show")
+        .map(|a| parse_slopes(a))
+        .collect::<Option<Vec<_>>>()
+        .map(|s| s.concat())
+        .unwrap_or_default();
+    if slopes.is_empty() {
+        return "Usage: day3survey <right,down>... [--show]".to_string();
+    }
+    let map = read_data();
+    let survey = map.survey(&slopes);
+    let mut report = survey.iter()
+        .map(|(slope, n)| format!("{}: {} trees", slope, n))
+        .join("\n");
+    if let Some((best, n)) = survey.iter().min_by_key(|(_, n)| *n) {
+        report.push_str(&format!("\nFewest trees: {} with {}", best, n));
+        if show {
+            report.push_str(&format!("\n{}", map.render_path(*best)));
+        }
+    }
+    report
 }
 
-fn move_right(s: &str, pos: usize, steps: usize) -> (usize, bool) {
-    let n = s.len();
-    let new_pos = (pos + steps) % n;
-    let is_tree = char_at(s, new_pos) == '#';
-    (new_pos, is_tree)
+/// Parses `right,down`, where each part is either a number or an inclusive range like `1..7`
+fn parse_slopes(s: &str) -> Option<Vec<Slope>> {
+    fn range(s: &str) -> Option<(i64, i64)> {
+        match s.split_once("..") {
+            Some((lo, hi)) => Some((lo.parse().ok()?, hi.parse().ok()?)),
+            None => s.parse().ok().map(|v| (v, v)),
+        }
+    }
+    let (right, down) = s.split_once(',')?;
+    let (r0, r1) = range(right)?;
+    let (d0, d1) = range(down)?;
+    if d0 < 1 {
+        return None;
+    }
+    Some((r0..=r1).cartesian_product(d0..=d1).map(|(r, d)| Slope::new(r, d as usize)).collect())
 }
 
-fn char_at(s: &str, pos: usize) -> char {
-    s.as_bytes()[pos] as char
+/// How far the toboggan moves right (or left, if negative) for every `down` rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slope {
+    pub right: i64,
+    pub down: usize,
+}
+
+impl Slope {
+    pub fn new(right: i64, down: usize) -> Self {
+        assert!(down > 0, "The toboggan has to go down the hill");
+        Self { right, down }
+    }
+}
+
+impl Display for Slope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "right {}, down {}", self.right, self.down)
+    }
+}
+
+/// The map of the hill, which repeats forever to the left and right
+pub struct TreeMap {
+    rows: Vec<Vec<bool>>,
+}
+
+impl TreeMap {
+    pub fn new(s: &str) -> Self {
+        let rows = s.lines()
+            .filter(|s| !s.is_empty())
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect();
+        Self { rows }
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows.first().map(|r| r.len()).unwrap_or(0)
+    }
+
+    /// The row and wrapped column of every square the toboggan lands on after the start
+    pub fn path(&self, slope: Slope) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (slope.down..self.rows.len())
+            .step_by(slope.down)
+            .enumerate()
+            .map(move |(i, row)| {
+                let width = self.rows[row].len() as i64;
+                (row, ((i as i64 + 1) * slope.right).rem_euclid(width) as usize)
+            })
+    }
+
+    pub fn trees_on(&self, slope: Slope) -> usize {
+        self.path(slope).filter(|&(row, col)| self.rows[row][col]).count()
+    }
+
+    pub fn survey(&self, slopes: &[Slope]) -> Vec<(Slope, usize)> {
+        slopes.iter().map(|&slope| (slope, self.trees_on(slope))).collect()
+    }
+
+    /// The map with the path overlaid: `O` where the toboggan lands on open snow and `X` where it hits a tree
+    pub fn render_path(&self, slope: Slope) -> String {
+        let mut grid = self.rows.iter()
+            .map(|row| row.iter().map(|&tree| if tree { '#' } else { '.' }).collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
+        if let Some(start) = grid.first_mut().and_then(|r| r.first_mut()) {
+            *start = 'S';
+        }
+        for (row, col) in self.path(slope) {
+            grid[row][col] = if self.rows[row][col] { 'X' } else { 'O' };
+        }
+        grid.into_iter().map(|r| r.into_iter().collect::<String>()).join("\n")
+    }
 }
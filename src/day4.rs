@@ -1,142 +1,299 @@
 use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::fs;
 
 pub fn day4a() -> String {
-    let passports = read_data();
+    let schema = Schema::load("assets/passport_schema.txt").unwrap();
+    let passports = read_data("assets/day4.txt");
     passports
         .iter()
-        .filter_map(|s| Passport::all_fields_present(s.as_str()))
+        .filter(|&p| schema.all_fields_present(p))
         .count()
         .to_string()
 }
 
 pub fn day4b() -> String {
-    let passports = read_data();
+    let schema = Schema::load("assets/passport_schema.txt").unwrap();
+    let passports = read_data("assets/day4.txt");
     passports
         .iter()
-        .filter_map(|s| Passport::new(s.as_str()))
+        .filter(|&p| schema.is_valid(p))
         .count()
         .to_string()
 }
 
-#[derive(Default, Debug)]
-pub struct Passport {
-    byr: String,
-    iyr: String,
-    eyr: String,
-    hgt: String,
-    hcl: String,
-    ecl: String,
-    pid: String,
-    cid: Option<String>, // (Country ID)
-}
-
-impl Passport {
-    fn new(s: &str) -> Option<Passport> {
-        Self::all_fields_present(s).and_then(Self::check_validity)
-    }
-
-    pub fn all_fields_present(s: &str) -> Option<Passport> {
-        let mut result = Passport::default();
-        let fields = s.split_whitespace().collect::<Vec<&str>>();
-        for field in fields {
-            let parts = field.split(':').collect::<Vec<&str>>();
-            if parts.len() == 2 {
-                match parts[0] {
-                    "byr" => result.byr = parts[1].to_string(),
-                    "iyr" => result.iyr = parts[1].to_string(),
-                    "eyr" => result.eyr = parts[1].to_string(),
-                    "hgt" => result.hgt = parts[1].to_string(),
-                    "hcl" => result.hcl = parts[1].to_string(),
-                    "ecl" => result.ecl = parts[1].to_string(),
-                    "pid" => result.pid = parts[1].to_string(),
-                    "cid" => result.cid = Some(parts[1].to_string()),
-                    _ => {}
-                }
+/// Validates a file of documents against a schema file, reporting each invalid document or, with `--summary`, totals
+pub fn day4validate(args: &[String]) -> String {
+    let (schema, documents, summary) = match args {
+        [schema, documents] => (schema, documents, false),
+        [schema, documents, flag] if flag == "--summary" => (schema, documents, true),
+        _ => return "Usage: day4validate <schema> <documents> [--summary]".to_string(),
+    };
+    let schema = match Schema::load(schema) {
+        Ok(s) => s,
+        Err(e) => return e.to_string(),
+    };
+    let documents = read_data(documents);
+    let reports = documents.iter().map(|d| schema.validate(d)).collect::<Vec<ValidationReport>>();
+    if !summary {
+        reports.iter()
+            .enumerate()
+            .filter(|(_, r)| !r.is_valid() || !r.unknown.is_empty())
+            .for_each(|(i, r)| println!("Document {}:\n{}", i + 1, r));
+    }
+    let valid = reports.iter().filter(|r| r.is_valid()).count();
+    format!("{} of {} documents are valid\n{}", valid, documents.len(), summarise(&schema, &reports))
+}
+
+/// How many documents failed each field's rule, or were missing it
+pub fn summarise(schema: &Schema, reports: &[ValidationReport]) -> String {
+    schema.fields.iter()
+        .map(|f| {
+            let missing = reports.iter().filter(|r| r.missing.contains(&f.name)).count();
+            let invalid = reports.iter().filter(|r| r.invalid.iter().any(|e| e.field == f.name)).count();
+            format!("{:<8} {:<40} missing: {:<5} invalid: {}", f.name, f.rule.to_string(), missing, invalid)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Schema line {}: {}", self.line, self.message)
+    }
+}
+
+/// What a field's value must look like
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Any,
+    Range(i64, i64),
+    /// Plain digits immediately followed by one of the units, and within that unit's bounds, e.g. `170cm`
+    Units(Vec<(String, i64, i64)>),
+    /// Matches the whole value
+    Regex(Regex),
+    OneOf(Vec<String>),
+}
+
+impl Rule {
+    fn parse(kind: &str, args: &[&str]) -> Result<Rule, String> {
+        let int = |s: &str| s.parse::<i64>().map_err(|_| format!("'{}' is not a number", s));
+        match (kind, args) {
+            ("any", []) => Ok(Rule::Any),
+            ("range", [min, max]) => Ok(Rule::Range(int(min)?, int(max)?)),
+            ("units", args) if !args.is_empty() && args.len() % 3 == 0 => args.chunks(3)
+                .map(|c| Ok((c[0].to_string(), int(c[1])?, int(c[2])?)))
+                .collect::<Result<Vec<_>, String>>()
+                .map(Rule::Units),
+            ("regex", [pattern]) => Regex::new(&format!("^(?:{})$", pattern))
+                .map(Rule::Regex)
+                .map_err(|e| format!("Invalid pattern: {}", e)),
+            ("enum", values) if !values.is_empty() => Ok(Rule::OneOf(values.iter().map(|s| s.to_string()).collect())),
+            ("any", _) | ("range", _) | ("units", _) | ("regex", _) | ("enum", _) => {
+                Err(format!("Wrong arguments for the '{}' rule", kind))
             }
+            _ => Err(format!("Unknown rule '{}'", kind)),
         }
-        if !result.byr.is_empty()
-            && !result.iyr.is_empty()
-            && !result.eyr.is_empty()
-            && !result.hgt.is_empty()
-            && !result.hcl.is_empty()
-            && !result.ecl.is_empty()
-            && !result.pid.is_empty()
-        {
-            Some(result)
-        } else {
-            None
+    }
+
+    pub fn check(&self, value: &str) -> bool {
+        match self {
+            Rule::Any => true,
+            Rule::Range(min, max) => matches!(value.parse::<i64>(), Ok(v) if v >= *min && v <= *max),
+            Rule::Units(units) => units.iter().any(|(unit, min, max)| {
+                value.strip_suffix(unit.as_str())
+                    .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .is_some_and(|v| v >= *min && v <= *max)
+            }),
+            Rule::Regex(re) => re.is_match(value),
+            Rule::OneOf(values) => values.iter().any(|v| v == value),
         }
     }
+}
 
-    fn check_validity(self) -> Option<Passport> {
-        if check_pid(&self.pid)
-            && check_byr(&self.byr)
-            && check_iyr(&self.iyr)
-            && check_eyr(&self.eyr)
-            && check_hgt(&self.hgt)
-            && check_hcl(&self.hcl)
-            && check_ecl(&self.ecl)
-        {
-            Some(self)
-        } else {
-            None
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::Any => f.write_str("any"),
+            Rule::Range(min, max) => write!(f, "range {} {}", min, max),
+            Rule::Units(units) => {
+                f.write_str("units")?;
+                units.iter().try_for_each(|(unit, min, max)| write!(f, " {} {} {}", unit, min, max))
+            }
+            // Leave off the anchors we added when compiling the pattern
+            Rule::Regex(re) => write!(f, "regex {}", &re.as_str()[4..re.as_str().len() - 2]),
+            Rule::OneOf(values) => write!(f, "enum {}", values.join(" ")),
         }
     }
 }
 
-fn check_byr(s: &str) -> bool {
-    matches!(s.parse::<usize>(), Ok(v) if v >= 1920 && v <= 2002)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub value: String,
+    pub rule: String,
 }
 
-fn check_iyr(s: &str) -> bool {
-    match s.parse::<usize>() {
-        Ok(v) => v >= 2010 && v <= 2020,
-        _ => false,
+/// Everything that is wrong with a document, including unknown fields, which don't make it invalid
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub missing: Vec<String>,
+    pub invalid: Vec<FieldError>,
+    pub unknown: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty() && self.invalid.is_empty()
     }
 }
 
-fn check_eyr(s: &str) -> bool {
-    match s.parse::<usize>() {
-        Ok(v) => v >= 2020 && v <= 2030,
-        _ => false,
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for field in &self.missing {
+            writeln!(f, "  missing {}", field)?;
+        }
+        for e in &self.invalid {
+            writeln!(f, "  {}:{} breaks rule '{}'", e.field, e.value, e.rule)?;
+        }
+        for field in &self.unknown {
+            writeln!(f, "  unknown field {}", field)?;
+        }
+        Ok(())
     }
 }
 
-fn check_hgt(s: &str) -> bool {
-    let re = Regex::new(r"^(\d*)(in|cm)$").unwrap();
-    match re.captures(s) {
-        Some(cap) => {
-            let val = cap[1].parse::<usize>().unwrap_or(0);
-            let unit = &cap[2];
-            match unit {
-                "cm" => val >= 150 && val <= 193,
-                "in" => val >= 59 && val <= 76,
-                _ => false,
+#[derive(Debug, Clone)]
+pub struct FieldSpec {
+    pub name: String,
+    pub required: bool,
+    pub rule: Rule,
+}
+
+/// The fields a document may have and the rules they must follow, in the format of `assets/passport_schema.txt`
+#[derive(Debug, Clone)]
+pub struct Schema {
+    fields: Vec<FieldSpec>,
+}
+
+impl Schema {
+    pub fn load(file: &str) -> Result<Self, SchemaError> {
+        let src = fs::read_to_string(file)
+            .map_err(|e| SchemaError { line: 0, message: format!("Could not read {}: {}", file, e) })?;
+        Self::parse(&src)
+    }
+
+    pub fn parse(src: &str) -> Result<Self, SchemaError> {
+        let mut fields: Vec<FieldSpec> = Vec::new();
+        for (i, line) in src.lines().enumerate() {
+            let error = |message: String| SchemaError { line: i + 1, message };
+            // Only whole lines can be comments, since patterns may contain '#'
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            let (name, required, kind, args) = match words.as_slice() {
+                [] => continue,
+                [name, required, kind, args @ ..] => (*name, *required, *kind, args),
+                _ => return Err(error("Expected <field> <required|optional> <rule>".to_string())),
+            };
+            let required = match required {
+                "required" => true,
+                "optional" => false,
+                s => return Err(error(format!("Expected 'required' or 'optional', not '{}'", s))),
+            };
+            if fields.iter().any(|f| f.name == name) {
+                return Err(error(format!("'{}' is defined twice", name)));
             }
+            // A pattern is the rest of the line, so that it may contain spaces
+            let pattern = [skip_words(line, 3).trim_end()];
+            let args = if kind == "regex" && !args.is_empty() { &pattern[..] } else { args };
+            let rule = Rule::parse(kind, args).map_err(error)?;
+            fields.push(FieldSpec { name: name.to_string(), required, rule });
         }
-        None => false,
+        Ok(Self { fields })
+    }
+
+    pub fn fields(&self) -> &[FieldSpec] {
+        &self.fields
+    }
+
+    pub fn all_fields_present(&self, doc: &Document) -> bool {
+        self.fields.iter().all(|f| !f.required || doc.get(&f.name).is_some())
+    }
+
+    /// Every required field is present, and every field in the schema that is present follows its rule
+    pub fn is_valid(&self, doc: &Document) -> bool {
+        self.validate(doc).is_valid()
+    }
+
+    pub fn validate(&self, doc: &Document) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for f in &self.fields {
+            match doc.get(&f.name) {
+                Some(value) if !f.rule.check(value) => report.invalid.push(FieldError {
+                    field: f.name.clone(),
+                    value: value.to_string(),
+                    rule: f.rule.to_string(),
+                }),
+                Some(_) => {}
+                None if f.required => report.missing.push(f.name.clone()),
+                None => {}
+            }
+        }
+        report.unknown = doc.field_names()
+            .filter(|name| !self.fields.iter().any(|f| f.name == *name))
+            .map(String::from)
+            .collect();
+        report.unknown.sort();
+        report
     }
 }
 
-fn check_hcl(s: &str) -> bool {
-    let re = Regex::new(r"^#[0-9a-f]{6}$").unwrap();
-    re.is_match(s)
+fn skip_words(s: &str, n: usize) -> &str {
+    let mut rest = s.trim_start();
+    for _ in 0..n {
+        rest = rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..].trim_start();
+    }
+    rest
 }
 
-fn check_ecl(s: &str) -> bool {
-    matches!(s, "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth")
+/// A set of `key:value` fields separated by whitespace
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    fields: HashMap<String, String>,
 }
 
-fn check_pid(s: &str) -> bool {
-    let re = Regex::new(r"^\d{9}$").unwrap();
-    re.is_match(s)
+impl Document {
+    pub fn new(s: &str) -> Self {
+        let fields = s.split_whitespace()
+            .filter_map(|field| field.split_once(':'))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Self { fields }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.fields.get(field).map(String::as_str)
+    }
+
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.fields.keys().map(String::as_str)
+    }
 }
 
-fn read_data() -> Vec<String> {
-    fs::read_to_string("assets/day4.txt")
+fn read_data(file: &str) -> Vec<Document> {
+    fs::read_to_string(file)
         .expect("Could not read file")
         .split("\n\n")
-        .map(|s| s.to_string())
-        .collect::<Vec<String>>()
+        .map(Document::new)
+        .collect::<Vec<Document>>()
 }
@@ -1,23 +1,25 @@
+use std::fmt::{Display, Formatter};
 use std::fs;
 
 pub fn day5a() -> String {
-    let cards = read_data();
-    cards.iter()
-        .map(|s| calc_id(s.as_str()))
-        .max()
-        .unwrap()
-        .to_string()
+    let plane = PlaneGeometry::default();
+    let passes = match read_data(&plane) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+    match passes.iter().map(|p| p.seat_id(&plane)).max() {
+        Some(id) => id.to_string(),
+        None => "No boarding passes".to_string(),
+    }
 }
 
 pub fn day5b() -> String {
-    let cards = read_data();
-    let mut seats = [false; 128 * 8];
-    cards.iter()
-        .for_each(|s| {
-            let id = calc_id(s);
-            seats[id] = true;
-        });
-    for i in 1..128*8-1 {
+    let plane = PlaneGeometry::default();
+    let seats = match read_data(&plane) {
+        Ok(passes) => plane.occupied(&passes),
+        Err(e) => return e,
+    };
+    for i in 1..seats.len() - 1 {
         if !seats[i] && seats[i-1] && seats[i+1] {
             return i.to_string();
         }
@@ -25,39 +27,157 @@ pub fn day5b() -> String {
     "No solution".to_string()
 }
 
-pub fn calc_id(s: &str) -> usize {
-    let row = find_row(s);
-    let seat = find_seat(s);
-    row * 8 + seat
+/// Shows which seats are taken, one row of the plane per line
+pub fn day5map() -> String {
+    let plane = PlaneGeometry::default();
+    read_data(&plane).map_or_else(|e| e, |passes| plane.render(&passes))
 }
 
-pub fn find_row(s: &str) -> usize {
-    find_index(128, 'F', 'B', &s[0..7])
+/// Turns seat ids back into boarding pass codes, one per line
+pub fn day5encode(args: &[String]) -> String {
+    let plane = PlaneGeometry::default();
+    let usage = format!("Usage: day5encode <seat id less than {}>...", plane.seats());
+    if args.is_empty() {
+        return usage;
+    }
+    args.iter()
+        .map(|s| s.parse::<usize>().ok().and_then(|id| BoardingPass::from_seat_id(id, &plane)))
+        .map(|pass| pass.map(|p| p.encode(&plane)).ok_or_else(|| usage.clone()))
+        .collect::<Result<Vec<String>, String>>()
+        .map_or_else(|e| e, |codes| codes.join("\n"))
 }
 
-pub fn find_seat(s: &str) -> usize {
-    find_index(8, 'L', 'R', &s[7..10])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassError {
+    WrongLength { expected: usize, found: usize },
+    /// The character at the (0-based) position isn't valid there
+    BadChar { position: usize, found: char },
 }
 
-pub fn find_index(len: usize, bottom: char, top: char, code: &str) -> usize {
-    let (low, _high, _rem) = code.chars()
-        .fold((0usize, len-1usize, len), |(mut low, mut high, mut rem), c| {
-            rem /= 2;
-            match c {
-                c if c == bottom => high -= rem,
-                c if c == top => low += rem,
-                _ => unreachable!()
+impl Display for PassError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PassError::WrongLength { expected, found } => {
+                write!(f, "Boarding passes have {} characters, not {}", expected, found)
             }
-            (low, high, rem)
-        });
-    low
+            PassError::BadChar { position, found } => write!(f, "Unexpected '{}' at position {}", found, position),
+        }
+    }
+}
+
+/// The size of the plane, in powers of two since every character of a boarding pass halves the range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneGeometry {
+    rows: usize,
+    cols: usize,
 }
 
-fn read_data() -> Vec<String> {
+impl Default for PlaneGeometry {
+    fn default() -> Self {
+        Self { rows: 128, cols: 8 }
+    }
+}
+
+impl PlaneGeometry {
+    pub fn new(rows: usize, cols: usize) -> Option<Self> {
+        if rows.is_power_of_two() && cols.is_power_of_two() { Some(Self { rows, cols }) } else { None }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The number of seats, which is also one more than the highest seat id
+    pub fn seats(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    fn row_chars(&self) -> usize {
+        self.rows.trailing_zeros() as usize
+    }
+
+    fn col_chars(&self) -> usize {
+        self.cols.trailing_zeros() as usize
+    }
+
+    /// Whether each seat id is taken
+    pub fn occupied(&self, passes: &[BoardingPass]) -> Vec<bool> {
+        let mut seats = vec![false; self.seats()];
+        passes.iter().for_each(|p| seats[p.seat_id(self)] = true);
+        seats
+    }
+
+    /// The seat map, with `#` for taken seats and `.` for empty ones
+    pub fn render(&self, passes: &[BoardingPass]) -> String {
+        let seats = self.occupied(passes);
+        let width = (self.rows - 1).to_string().len();
+        seats.chunks(self.cols)
+            .enumerate()
+            .map(|(row, seats)| {
+                let seats = seats.iter().map(|&s| if s { '#' } else { '.' }).collect::<String>();
+                format!("{:>width$} {}", row, seats, width = width)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardingPass {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl BoardingPass {
+    /// Reads a code like `FBFBBFFRLR`, which is the binary seat position with `B` and `R` as the ones
+    pub fn parse(s: &str, plane: &PlaneGeometry) -> Result<Self, PassError> {
+        let expected = plane.row_chars() + plane.col_chars();
+        let found = s.chars().count();
+        if found != expected {
+            return Err(PassError::WrongLength { expected, found });
+        }
+        let (mut row, mut col) = (0, 0);
+        for (position, c) in s.chars().enumerate() {
+            let in_row = position < plane.row_chars();
+            match (in_row, c) {
+                (true, 'F') => row <<= 1,
+                (true, 'B') => row = (row << 1) | 1,
+                (false, 'L') => col <<= 1,
+                (false, 'R') => col = (col << 1) | 1,
+                _ => return Err(PassError::BadChar { position, found: c }),
+            }
+        }
+        Ok(Self { row, col })
+    }
+
+    pub fn encode(&self, plane: &PlaneGeometry) -> String {
+        let bits = |v: usize, n: usize, zero: char, one: char| {
+            (0..n).rev().map(move |i| if v & (1 << i) > 0 { one } else { zero })
+        };
+        bits(self.row, plane.row_chars(), 'F', 'B')
+            .chain(bits(self.col, plane.col_chars(), 'L', 'R'))
+            .collect()
+    }
+
+    pub fn seat_id(&self, plane: &PlaneGeometry) -> usize {
+        self.row * plane.cols() + self.col
+    }
+
+    pub fn from_seat_id(id: usize, plane: &PlaneGeometry) -> Option<Self> {
+        if id < plane.seats() { Some(Self { row: id / plane.cols(), col: id % plane.cols() }) } else { None }
+    }
+}
+
+fn read_data(plane: &PlaneGeometry) -> Result<Vec<BoardingPass>, String> {
     fs::read_to_string("assets/day5.txt")
         .expect("Could not read file")
-        .split('\n')
-        .filter(|&s| s.len() == 10)
-        .map(String::from)
+        .lines()
+        .enumerate()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(i, s)| BoardingPass::parse(s, plane).map_err(|e| format!("Line {}: {}", i + 1, e)))
         .collect()
-}
\ No newline at end of file
+}
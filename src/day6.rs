@@ -1,45 +1,216 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::fs;
+use itertools::Itertools;
 
 pub fn day6a() -> String {
-    let groups = read_data();
+    let (alphabet, groups) = read_data();
     groups.iter()
-        .map(| group| {
-            count_answers(group.as_str())
-        }).sum::<usize>().to_string()
+        .map(|group| group.union(&alphabet).len())
+        .sum::<usize>()
+        .to_string()
 }
 
 pub fn day6b() -> String {
-    let groups = read_data();
+    let (alphabet, groups) = read_data();
     groups.iter()
-        .map( | group| {
-            count_all_answered(group.as_str())
-        }).sum::<usize>().to_string()
-}
-
-fn count_all_answered(s: &str) -> usize {
-    // Assuming no-one answers the same question more than once
-    let groups_size = s.split('\n').filter(|s| !s.is_empty()).count();
-    let mut result = [0usize; 26];
-    s.chars().filter(|c| *c >= 'a' && *c <= 'z')
-        .for_each(|c| {
-            let index = c as usize - 97;
-            result[index] += 1
-        });
-    result.iter().filter(|&&elem| elem == groups_size).count()
-}
-
-fn count_answers(s: &str) -> usize {
-    let mut result = HashSet::new();
-    s.chars().filter(|c| *c >= 'a' && *c <= 'z')
-        .for_each(|c| { result.insert(c); });
-    result.len()
-}
-
-fn read_data() -> Vec<String> {
-    fs::read_to_string("assets/day6.txt")
-        .expect("Could not read file")
-        .split("\n\n")
-        .map(String::from)
-        .collect::<Vec<String>>()
-}
\ No newline at end of file
+        .map(|group| group.intersection(&alphabet).len())
+        .sum::<usize>()
+        .to_string()
+}
+
+/// Per-question statistics across all the groups, optionally with the questions at least `k` members answered
+pub fn day6stats(args: &[String]) -> String {
+    let k = match args {
+        [] => None,
+        [k] => match k.parse::<usize>() {
+            Ok(k) => Some(k),
+            Err(_) => return "Usage: day6stats [k]".to_string(),
+        },
+        _ => return "Usage: day6stats [k]".to_string(),
+    };
+    let (alphabet, groups) = read_data();
+    let mut report = QuestionStats::collect(&alphabet, &groups)
+        .iter()
+        .map(|s| format!("{}: {} answers, anyone in {} groups, everyone in {} groups, odd number in {} groups",
+                         s.question, s.answers, s.groups_any, s.groups_all, s.groups_odd))
+        .join("\n");
+    if let Some(k) = k {
+        let n = groups.iter().map(|g| g.at_least(k, &alphabet).len()).sum::<usize>();
+        report.push_str(&format!("\nAnswered by at least {} members: {}", k, n));
+    }
+    report
+}
+
+/// The questions that can be answered, each a position in an `AnswerSet`
+#[derive(Debug, Clone, Default)]
+pub struct Alphabet {
+    questions: Vec<char>,
+    index: HashMap<char, usize>,
+}
+
+impl Alphabet {
+    /// The alphabet of every character in `s`, ignoring whitespace, in sorted order
+    pub fn from_answers(s: &str) -> Self {
+        let questions = s.chars().filter(|c| !c.is_whitespace()).unique().sorted().collect::<Vec<char>>();
+        let index = questions.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        Self { questions, index }
+    }
+
+    pub fn len(&self) -> usize {
+        self.questions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.questions.is_empty()
+    }
+
+    pub fn questions(&self) -> &[char] {
+        &self.questions
+    }
+
+    /// The set of the characters in `s` that are in the alphabet
+    pub fn answer_set(&self, s: &str) -> AnswerSet {
+        let mut set = AnswerSet::empty(self);
+        s.chars().filter_map(|c| self.index.get(&c)).for_each(|&i| set.insert(i));
+        set
+    }
+}
+
+/// A set of answered questions, as a bitset over an `Alphabet`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnswerSet {
+    blocks: Vec<u64>,
+}
+
+impl AnswerSet {
+    pub fn empty(alphabet: &Alphabet) -> Self {
+        Self { blocks: vec![0; alphabet.len().div_ceil(64)] }
+    }
+
+    pub fn full(alphabet: &Alphabet) -> Self {
+        let mut set = Self::empty(alphabet);
+        (0..alphabet.len()).for_each(|i| set.insert(i));
+        set
+    }
+
+    pub fn insert(&mut self, i: usize) {
+        self.blocks[i / 64] |= 1 << (i % 64);
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.blocks[i / 64] & (1 << (i % 64)) > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|&b| b == 0)
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>(&self, other: &AnswerSet, f: F) -> AnswerSet {
+        let blocks = self.blocks.iter().zip(&other.blocks).map(|(&a, &b)| f(a, b)).collect();
+        AnswerSet { blocks }
+    }
+
+    pub fn union(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a & b)
+    }
+
+    pub fn symmetric_difference(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a ^ b)
+    }
+
+    /// The answered questions, as characters of the alphabet
+    pub fn to_chars<'a>(&'a self, alphabet: &'a Alphabet) -> impl Iterator<Item = char> + 'a {
+        (0..alphabet.len()).filter(move |&i| self.contains(i)).map(move |i| alphabet.questions[i])
+    }
+}
+
+/// The answers of each member of a group
+#[derive(Debug, Clone)]
+pub struct GroupAnswers {
+    members: Vec<AnswerSet>,
+}
+
+impl GroupAnswers {
+    /// One member per line
+    pub fn new(s: &str, alphabet: &Alphabet) -> Self {
+        let members = s.lines().filter(|l| !l.trim().is_empty()).map(|l| alphabet.answer_set(l)).collect();
+        Self { members }
+    }
+
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Questions anyone in the group answered
+    pub fn union(&self, alphabet: &Alphabet) -> AnswerSet {
+        self.members.iter().fold(AnswerSet::empty(alphabet), |acc, m| acc.union(m))
+    }
+
+    /// Questions everyone in the group answered
+    pub fn intersection(&self, alphabet: &Alphabet) -> AnswerSet {
+        if self.members.is_empty() {
+            return AnswerSet::empty(alphabet);
+        }
+        self.members.iter().fold(AnswerSet::full(alphabet), |acc, m| acc.intersection(m))
+    }
+
+    /// Questions answered by an odd number of members
+    pub fn symmetric_difference(&self, alphabet: &Alphabet) -> AnswerSet {
+        self.members.iter().fold(AnswerSet::empty(alphabet), |acc, m| acc.symmetric_difference(m))
+    }
+
+    /// Questions answered by at least `k` members
+    pub fn at_least(&self, k: usize, alphabet: &Alphabet) -> AnswerSet {
+        let mut set = AnswerSet::empty(alphabet);
+        (0..alphabet.len())
+            .filter(|&i| self.members.iter().filter(|m| m.contains(i)).count() >= k)
+            .for_each(|i| set.insert(i));
+        set
+    }
+}
+
+/// How one question was answered across all the groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuestionStats {
+    pub question: char,
+    pub answers: usize,
+    pub groups_any: usize,
+    pub groups_all: usize,
+    pub groups_odd: usize,
+}
+
+impl QuestionStats {
+    pub fn collect(alphabet: &Alphabet, groups: &[GroupAnswers]) -> Vec<QuestionStats> {
+        let sets = groups.iter()
+            .map(|g| (g.union(alphabet), g.intersection(alphabet), g.symmetric_difference(alphabet)))
+            .collect::<Vec<_>>();
+        alphabet.questions.iter()
+            .enumerate()
+            .map(|(i, &question)| QuestionStats {
+                question,
+                answers: groups.iter().flat_map(|g| &g.members).filter(|m| m.contains(i)).count(),
+                groups_any: sets.iter().filter(|(any, _, _)| any.contains(i)).count(),
+                groups_all: sets.iter().filter(|(_, all, _)| all.contains(i)).count(),
+                groups_odd: sets.iter().filter(|(_, _, odd)| odd.contains(i)).count(),
+            })
+            .collect()
+    }
+}
+
+fn read_data() -> (Alphabet, Vec<GroupAnswers>) {
+    let data = fs::read_to_string("assets/day6.txt").expect("Could not read file");
+    let alphabet = Alphabet::from_answers(&data);
+    let groups = data.split("\n\n")
+        .filter(|g| !g.trim().is_empty())
+        .map(|g| GroupAnswers::new(g, &alphabet))
+        .collect();
+    (alphabet, groups)
+}
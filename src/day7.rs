@@ -1,39 +1,312 @@
 use std::fs;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::fmt::{Display, Formatter};
+use itertools::Itertools;
 
 pub fn day7a() -> String {
     let bags = read_data();
-    let mybag = "shiny gold";
-    bags.values()
-        .filter(|b| b.can_ultimately_hold(mybag, &bags))
-        .count()
-        .to_string()
+    let result = BagGraph::new(&bags).and_then(|graph| graph.holders_of("shiny gold").map(|h| h.len()));
+    match result {
+        Ok(n) => n.to_string(),
+        Err(e) => e.to_string(),
+    }
 }
 
 pub fn day7b() -> String {
     let bags = read_data();
-    let mybag = "shiny gold";
-    count_bags(bags.get(mybag).unwrap(), &bags).to_string()
+    match BagGraph::new(&bags).and_then(|graph| graph.total_contents("shiny gold")) {
+        Ok(n) => n.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Writes the bag rules out as Graphviz DOT or JSON, optionally only `from <color>` or `to <color>`
+pub fn day7export(args: &[String]) -> String {
+    let bags = read_data();
+    let scope = match (args.get(1).map(String::as_str), args.get(2)) {
+        (None, _) => ExportScope::All,
+        (Some("from"), Some(color)) => ExportScope::ReachableFrom(color),
+        (Some("to"), Some(color)) => ExportScope::Reaching(color),
+        _ => return "Usage: day7export <dot|json> [from|to <color>]".to_string(),
+    };
+    let result = match args.first().map(String::as_str) {
+        Some("dot") => to_dot(&bags, &scope),
+        Some("json") => to_json(&bags, &scope),
+        _ => return "Usage: day7export <dot|json> [from|to <color>]".to_string(),
+    };
+    result.unwrap_or_else(|e| e.to_string())
 }
 
-fn count_bags(bag: &Bag, set: &HashMap<String, Bag>) -> usize {
-    if bag.contains.is_empty() {
-        println!("{} is empty", bag.color);
-        return 0;
+/// Which part of the rules to export
+pub enum ExportScope<'a> {
+    All,
+    ReachableFrom(&'a str),
+    Reaching(&'a str),
+}
+
+/// The rules in Graphviz DOT format, with an edge from each bag to the bags it contains
+pub fn to_dot(bags: &HashMap<String, Bag>, scope: &ExportScope) -> Result<String, BagGraphError> {
+    let selected = select_bags(bags, scope)?;
+    let mut dot = String::from("digraph bags {\n");
+    for bag in &selected {
+        dot.push_str(&format!("    \"{}\";\n", escape(&bag.color)));
+    }
+    for bag in &selected {
+        for (n, inner) in bag.contains.iter().filter(|(_, c)| selected.iter().any(|b| &b.color == c)) {
+            dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"{}\"];\n", escape(&bag.color), escape(inner), n));
+        }
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+/// The rules as a JSON object mapping each colour to a list of `{"count", "color"}` pairs
+pub fn to_json(bags: &HashMap<String, Bag>, scope: &ExportScope) -> Result<String, BagGraphError> {
+    let selected = select_bags(bags, scope)?;
+    let entries = selected.iter()
+        .map(|bag| {
+            let contents = bag.contains.iter()
+                .filter(|(_, c)| selected.iter().any(|b| &b.color == c))
+                .map(|(n, c)| format!("{{\"count\": {}, \"color\": \"{}\"}}", n, escape(c)))
+                .join(", ");
+            format!("  \"{}\": [{}]", escape(&bag.color), contents)
+        })
+        .join(",\n");
+    Ok(format!("{{\n{}\n}}\n", entries))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The bags that fall inside `scope`, sorted by colour so that the output is stable
+fn select_bags<'a>(bags: &'a HashMap<String, Bag>, scope: &ExportScope) -> Result<Vec<&'a Bag>, BagGraphError> {
+    let (start, forward) = match scope {
+        ExportScope::All => return Ok(bags.values().sorted_by(|a, b| a.color.cmp(&b.color)).collect()),
+        ExportScope::ReachableFrom(color) => (*color, true),
+        ExportScope::Reaching(color) => (*color, false),
+    };
+    if !bags.contains_key(start) {
+        return Err(BagGraphError::UnknownColor(start.to_string()));
+    }
+    // Walk the rules directly rather than through a `BagGraph`, so that rules containing cycles can still be
+    // exported and inspected.
+    let mut holders: HashMap<&str, Vec<&str>> = HashMap::new();
+    for bag in bags.values() {
+        for (_, inner) in &bag.contains {
+            holders.entry(inner.as_str()).or_default().push(bag.color.as_str());
+        }
+    }
+    let mut found = HashSet::new();
+    let mut queue = VecDeque::from(vec![start]);
+    found.insert(start);
+    while let Some(color) = queue.pop_front() {
+        let next: Vec<&str> = if forward {
+            bags.get(color).map(|b| b.contains.iter().map(|(_, c)| c.as_str()).collect()).unwrap_or_default()
+        } else {
+            holders.get(color).cloned().unwrap_or_default()
+        };
+        for c in next {
+            if found.insert(c) {
+                queue.push_back(c);
+            }
+        }
+    }
+    Ok(found.into_iter()
+        .filter_map(|c| bags.get(c))
+        .sorted_by(|a, b| a.color.cmp(&b.color))
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BagGraphError {
+    /// The rules refer to themselves, through these colours in order
+    Cycle(Vec<String>),
+    UnknownColor(String),
+}
+
+impl Display for BagGraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cycle(colors) => write!(f, "Bag rules contain a cycle: {}", colors.join(" -> ")),
+            Self::UnknownColor(color) => write!(f, "There are no rules for {} bags", color),
+        }
+    }
+}
+
+/// The bag rules as a graph, indexed both ways
+pub struct BagGraph {
+    colors: Vec<String>,
+    index: HashMap<String, usize>,
+    contains: Vec<Vec<(usize, usize)>>,
+    contained_by: Vec<Vec<usize>>,
+    // Filled in once in topological order, saturating since nesting grows these exponentially
+    totals: Vec<u128>,
+    holders: RefCell<HashMap<usize, Rc<HashSet<usize>>>>,
+}
+
+impl BagGraph {
+    pub fn new(bags: &HashMap<String, Bag>) -> Result<Self, BagGraphError> {
+        let mut graph = Self {
+            colors: Vec::new(),
+            index: HashMap::new(),
+            contains: Vec::new(),
+            contained_by: Vec::new(),
+            totals: Vec::new(),
+            holders: RefCell::new(HashMap::new()),
+        };
+        // Sort the colours so that node ids don't depend on the hash map ordering
+        for bag in bags.values().sorted_by(|a, b| a.color.cmp(&b.color)) {
+            let outer = graph.node(&bag.color);
+            for (n, color) in &bag.contains {
+                let inner = graph.node(color);
+                graph.contains[outer].push((*n, inner));
+                graph.contained_by[inner].push(outer);
+            }
+        }
+        let order = graph.topological_order()?;
+        graph.totals = vec![0; graph.colors.len()];
+        for &i in &order {
+            graph.totals[i] = graph.contains[i]
+                .iter()
+                .map(|&(n, j)| (n as u128).saturating_mul(graph.totals[j].saturating_add(1)))
+                .fold(0u128, |tot, n| tot.saturating_add(n));
+        }
+        Ok(graph)
+    }
+
+    fn node(&mut self, color: &str) -> usize {
+        if let Some(&i) = self.index.get(color) {
+            return i;
+        }
+        let i = self.colors.len();
+        self.colors.push(color.to_string());
+        self.index.insert(color.to_string(), i);
+        self.contains.push(Vec::new());
+        self.contained_by.push(Vec::new());
+        i
+    }
+
+    fn id(&self, color: &str) -> Result<usize, BagGraphError> {
+        self.index.get(color).copied().ok_or_else(|| BagGraphError::UnknownColor(color.to_string()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = &str> {
+        self.colors.iter().map(String::as_str)
+    }
+
+    /// The bags directly inside a `color` bag, along with how many of each
+    pub fn contents_of(&self, color: &str) -> Result<Vec<(usize, &str)>, BagGraphError> {
+        let i = self.id(color)?;
+        Ok(self.contains[i].iter().map(|&(n, j)| (n, self.colors[j].as_str())).collect())
+    }
+
+    /// The bags that directly hold a `color` bag
+    pub fn direct_holders_of(&self, color: &str) -> Result<Vec<&str>, BagGraphError> {
+        let i = self.id(color)?;
+        Ok(self.contained_by[i].iter().map(|&j| self.colors[j].as_str()).collect())
+    }
+
+    /// Every bag that can ultimately hold a `color` bag
+    pub fn holders_of(&self, color: &str) -> Result<HashSet<&str>, BagGraphError> {
+        let i = self.id(color)?;
+        Ok(self.ancestors(i).iter().map(|&j| self.colors[j].as_str()).collect())
+    }
+
+    /// Every bag that can ultimately be found inside a `color` bag
+    pub fn reachable_from(&self, color: &str) -> Result<HashSet<&str>, BagGraphError> {
+        let i = self.id(color)?;
+        let found = Self::walk(i, |j| self.contains[j].iter().map(|&(_, k)| k).collect());
+        Ok(found.into_iter().map(|j| self.colors[j].as_str()).collect())
+    }
+
+    /// Whether `outer` can ultimately hold an `inner` bag
+    pub fn can_hold(&self, outer: &str, inner: &str) -> Result<bool, BagGraphError> {
+        let outer = self.id(outer)?;
+        let inner = self.id(inner)?;
+        Ok(self.ancestors(inner).contains(&outer))
+    }
+
+    /// The total number of bags inside a `color` bag, or `u128::MAX` if there are at least that many
+    pub fn total_contents(&self, color: &str) -> Result<u128, BagGraphError> {
+        let i = self.id(color)?;
+        Ok(self.totals[i])
+    }
+
+    fn ancestors(&self, i: usize) -> Rc<HashSet<usize>> {
+        if let Some(found) = self.holders.borrow().get(&i) {
+            return Rc::clone(found);
+        }
+        let found = Rc::new(Self::walk(i, |j| self.contained_by[j].clone()));
+        self.holders.borrow_mut().insert(i, Rc::clone(&found));
+        found
+    }
+
+    fn walk<F: Fn(usize) -> Vec<usize>>(start: usize, next: F) -> HashSet<usize> {
+        let mut found = HashSet::new();
+        let mut queue = VecDeque::from(vec![start]);
+        while let Some(i) = queue.pop_front() {
+            for j in next(i) {
+                if found.insert(j) {
+                    queue.push_back(j);
+                }
+            }
+        }
+        found
+    }
+
+    /// Every bag after all the bags it contains, or the cycle if there is one
+    fn topological_order(&self) -> Result<Vec<usize>, BagGraphError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark { New, Active, Done }
+        fn visit(graph: &BagGraph, i: usize, marks: &mut [Mark], path: &mut Vec<usize>, order: &mut Vec<usize>)
+                 -> Result<(), BagGraphError> {
+            match marks[i] {
+                Mark::Done => return Ok(()),
+                Mark::Active => {
+                    let start = path.iter().position(|&j| j == i).unwrap();
+                    let cycle = path[start..].iter()
+                        .chain(std::iter::once(&i))
+                        .map(|&j| graph.colors[j].clone())
+                        .collect();
+                    return Err(BagGraphError::Cycle(cycle));
+                }
+                Mark::New => {}
+            }
+            marks[i] = Mark::Active;
+            path.push(i);
+            for &(_, j) in &graph.contains[i] {
+                visit(graph, j, marks, path, order)?;
+            }
+            path.pop();
+            marks[i] = Mark::Done;
+            order.push(i);
+            Ok(())
+        }
+
+        let mut marks = vec![Mark::New; self.colors.len()];
+        let mut order = Vec::with_capacity(self.colors.len());
+        let mut path = Vec::new();
+        for i in 0..self.colors.len() {
+            visit(self, i, &mut marks, &mut path, &mut order)?;
+        }
+        Ok(order)
     }
-    println!("[{}] has {} inner bags: {:?}", bag.color, bag.contains.len(), &bag.contains);
-    let n = bag.contains.iter()
-        .fold(0usize, |tot, (n, b)| {
-            let inner_count = count_bags(set.get(b.as_str()).unwrap(), set);
-            tot + n * (1 + inner_count)
-        });
-    println!("{} contains {}\n", bag.color, n);
-    n
 }
 
 #[derive(Debug)]
-struct Bag {
+pub struct Bag {
     color: String,
     contains: Vec<(usize, String)>,
 }
@@ -49,22 +322,9 @@ impl Bag {
     pub fn can_contain(&mut self, n: usize, bag: &str) {
         self.contains.push((n, bag.to_string()))
     }
-
-    pub fn can_ultimately_hold(&self, color: &str, set: &HashMap<String, Bag>) -> bool {
-        if self.contains.is_empty() {
-            return false;
-        }
-        self.contains.iter()
-            .any(|(_, b)| {
-                b.as_str() == color ||
-                    set.get(b.as_str())
-                        .unwrap()
-                        .can_ultimately_hold(color, set)
-            })
-    }
 }
 
-fn read_data() -> HashMap<String, Bag> {
+pub fn read_data() -> HashMap<String, Bag> {
     let factory = BagFactory::default();
     let mut set = HashMap::new();
     fs::read_to_string("assets/day7.txt")
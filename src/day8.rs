@@ -1,4 +1,9 @@
 use std::fs;
+use std::io::{self, BufRead, Write};
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
+use std::collections::{HashSet, VecDeque};
+use crate::handheld::{split_labels, is_label, AsmError, Exit, InstructionSet, Machine, Operand, MAX_STEPS};
 
 pub fn day8a() -> String {
     let stack = read_data();
@@ -14,112 +19,806 @@ pub fn day8b() -> String {
     let stack = read_data();
     let mut vm = VM::new();
     vm.load_instructions(stack);
-    vm.self_fix()
+    match vm.repair() {
+        Some(fix) => format!("Fixed - {}", fix.accumulator),
+        None => "No solution".to_string(),
+    }
 }
 
-#[derive(Clone)]
-enum Instruction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
     Acc(i32),
     Jmp(i32),
     Nop(i32),
 }
 
 impl Instruction {
-    fn from_str(s: &str) -> Self {
-        let ins = &s[0..3];
-        let val = s[4..].parse::<i32>().unwrap();
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Acc(_) => "acc",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Nop(_) => "nop",
+        }
+    }
+
+    pub fn arg(&self) -> i32 {
+        match self {
+            Instruction::Acc(v) | Instruction::Jmp(v) | Instruction::Nop(v) => *v,
+        }
+    }
+
+    /// The instruction with `jmp` and `nop` swapped, or `None` for `acc`
+    pub fn flipped(&self) -> Option<Instruction> {
+        match *self {
+            Instruction::Acc(_) => None,
+            Instruction::Jmp(val) => Some(Instruction::Nop(val)),
+            Instruction::Nop(val) => Some(Instruction::Jmp(val)),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:+}", self.mnemonic(), self.arg())
+    }
+}
+
+/// A single-instruction fix that makes a program terminate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    pub address: usize,
+    pub original: Instruction,
+    pub accumulator: i32,
+}
+
+/// Turns program text into instructions, allowing `#` or `;` comments and `label:` jump targets
+pub fn assemble(src: &str) -> Result<Vec<Instruction>, AsmError> {
+    let (labels, lines) = split_labels(src)?;
+    // Second pass: parse the instructions, resolving labels to relative offsets
+    lines.iter()
+        .enumerate()
+        .map(|(addr, &(line_no, line))| {
+            let mut parts = line.split_whitespace();
+            let op = parts.next().unwrap_or_default();
+            let arg = parts.next()
+                .ok_or_else(|| AsmError::new(line_no, format!("'{}' is missing its argument", op)))?;
+            if let Some(extra) = parts.next() {
+                return Err(AsmError::new(line_no, format!("Unexpected '{}' after argument", extra)));
+            }
+            let val = match arg.parse::<i32>() {
+                Ok(v) => v,
+                Err(_) if op != "acc" && is_label(arg) => {
+                    let target = labels.get(arg)
+                        .ok_or_else(|| AsmError::new(line_no, format!("Unknown label '{}'", arg)))?;
+                    *target as i32 - addr as i32
+                }
+                Err(_) => return Err(AsmError::new(line_no, format!("'{}' is not a valid argument", arg))),
+            };
+            match op {
+                "acc" => Ok(Instruction::Acc(val)),
+                "jmp" => Ok(Instruction::Jmp(val)),
+                "nop" => Ok(Instruction::Nop(val)),
+                _ => Err(AsmError::new(line_no, format!("Unknown instruction '{}'", op))),
+            }
+        })
+        .collect()
+}
+
+/// Turns instructions back into program text, with labelled jump targets, that `assemble` reads back the same
+pub fn disassemble(program: &[Instruction]) -> String {
+    let target = |addr: usize, ins: &Instruction| -> Option<usize> {
         match ins {
-            "acc" => Instruction::Acc(val),
-            "jmp" => Instruction::Jmp(val),
-            "nop" => Instruction::Nop(val),
-            _ => panic!("Unknown instruction"),
+            Instruction::Acc(_) => None,
+            _ => {
+                let t = addr as i64 + ins.arg() as i64;
+                if t >= 0 && t as usize <= program.len() { Some(t as usize) } else { None }
+            }
+        }
+    };
+    let labelled = program.iter()
+        .enumerate()
+        .filter_map(|(addr, ins)| target(addr, ins))
+        .collect::<HashSet<usize>>();
+    let mut out = String::new();
+    for (addr, ins) in program.iter().enumerate() {
+        if labelled.contains(&addr) {
+            out.push_str(&format!("L{}:\n", addr));
         }
+        let text = match target(addr, ins) {
+            Some(t) => format!("{} L{}", ins.mnemonic(), t),
+            None => ins.to_string(),
+        };
+        let comment = match ins {
+            Instruction::Acc(_) => format!("# {:04}", addr),
+            _ => format!("# {:04} -> {}", addr, addr as i64 + ins.arg() as i64),
+        };
+        out.push_str(&format!("    {:<12}{}\n", text, comment));
+    }
+    if labelled.contains(&program.len()) {
+        out.push_str(&format!("L{}:\n", program.len()));
     }
+    out
 }
 
-struct VM {
+/// The original three-opcode VM, running its programs on the handheld `Machine`
+#[derive(Default)]
+pub struct VM {
     stack: Vec<Instruction>,
-    pointer: usize,
-    accumulator: i32,
-    marker: Vec<bool>,
+    machine: Machine,
+    marker: Vec<usize>,
 }
 
 impl VM {
     pub fn new() -> Self {
-        Self {
-            stack: Vec::new(),
-            accumulator: 0,
-            pointer: 0,
-            marker: Vec::new(),
-        }
+        Self::default()
     }
 
-    pub fn load_instructions(&mut self, stack: Vec<String>) {
-        self.stack = stack.iter()
-            .map(|s| Instruction::from_str(s.as_str()))
+    pub fn load_instructions(&mut self, stack: Vec<Instruction>) {
+        let set = InstructionSet::classic();
+        let ops = stack.iter()
+            .map(|ins| set.op(ins.mnemonic(), vec![Operand::Number(ins.arg())]).expect("acc, jmp and nop are classic"))
             .collect();
+        self.machine = Machine::new(ops);
+        self.stack = stack;
+    }
+
+    pub fn reset(&mut self) {
+        self.machine.reset();
+        self.marker = vec![0; self.stack.len()];
     }
 
+    pub fn accumulator(&self) -> i32 {
+        self.machine.memory.get("acc")
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.machine.pointer()
+    }
+
+    /// Runs the program from the start, returning the accumulator as `Err` if it loops forever or faults
     pub fn run(&mut self) -> Result<i32, i32> {
-        self.accumulator = 0;
-        self.pointer = 0;
-        self.marker = vec![false; self.stack.len()];
+        self.reset();
         loop {
             match self.get_next() {
-                Ok(Some(next)) => self.execute(next),
+                Ok(Some(_)) => {
+                    if let Some(Exit::Fault { .. }) = self.execute() {
+                        return Err(self.accumulator());
+                    }
+                }
                 Ok(None) => { println!("Ok"); break; },
-                Err(_) => return Err(self.accumulator),
+                Err(_) => return Err(self.accumulator()),
+            }
+        }
+        Ok(self.accumulator())
+    }
+
+    pub fn run_traced(&mut self) -> Trace {
+        self.reset();
+        let mut steps = Vec::new();
+        loop {
+            match self.get_next() {
+                Ok(Some(next)) => {
+                    let pointer = self.pointer();
+                    let fault = matches!(self.execute(), Some(Exit::Fault { .. }));
+                    steps.push(TraceEntry { pointer, instruction: next, accumulator: self.accumulator() });
+                    if fault {
+                        return Trace { steps, outcome: Err(Stopped::Fault(pointer)) };
+                    }
+                }
+                Ok(None) => return Trace { steps, outcome: Ok(self.accumulator()) },
+                Err(_) => {
+                    // Every address runs at most once before we get here, so the first visit is unique
+                    let start = steps.iter().position(|s| s.pointer == self.pointer()).unwrap();
+                    let report = LoopReport {
+                        accumulator: self.accumulator(),
+                        cycle: steps[start..].iter().map(|s| s.pointer).collect(),
+                        reentry: *steps.last().unwrap(),
+                    };
+                    return Trace { steps, outcome: Err(Stopped::Loop(report)) };
+                }
             }
         }
-        Ok(self.accumulator)
+    }
+
+    /// Finds the `jmp`/`nop` flip that makes the program terminate by running every candidate program
+    pub fn repair_brute_force(&self) -> Option<Repair> {
+        self.stack.iter()
+            .enumerate()
+            .filter_map(|(i, ins)| ins.flipped().map(|f| (i, f)))
+            .find_map(|(i, flipped)| {
+                let mut stack = self.stack.clone();
+                stack[i] = flipped;
+                let mut vm = VM::new();
+                vm.load_instructions(stack);
+                vm.run().ok().map(|accumulator| Repair { address: i, original: self.stack[i], accumulator })
+            })
     }
 
     pub fn self_fix(&mut self) -> String {
-        for (i, ins) in self.stack.iter().enumerate() {
-            let mut new_stack = self.stack.clone();
-            match ins {
-                Instruction::Acc(_) => continue,
-                Instruction::Jmp(val) => { new_stack[i] = Instruction::Nop(*val); },
-                Instruction::Nop(val) => { new_stack[i] = Instruction::Jmp(*val); },
+        match self.repair_brute_force() {
+            Some(fix) => format!("Fixed - {}", fix.accumulator),
+            None => "No solution".to_string(),
+        }
+    }
+
+    fn successor(&self, addr: usize, ins: Instruction) -> Option<usize> {
+        let next = match ins {
+            Instruction::Jmp(val) => addr as i64 + val as i64,
+            _ => addr as i64 + 1,
+        };
+        if next >= 0 && (next as usize) < self.stack.len() { Some(next as usize) } else { None }
+    }
+
+    /// Finds the `jmp`/`nop` flip that makes the program terminate in linear time, using the control-flow graph
+    pub fn repair(&self) -> Option<Repair> {
+        // Walk backwards from the exits to find every address that already leads to termination
+        let n = self.stack.len();
+        let mut predecessors = vec![Vec::new(); n];
+        let mut terminates = vec![false; n];
+        let mut queue = VecDeque::new();
+        for (addr, &ins) in self.stack.iter().enumerate() {
+            match self.successor(addr, ins) {
+                Some(next) => predecessors[next].push(addr),
+                None => {
+                    terminates[addr] = true;
+                    queue.push_back(addr);
+                }
             }
-            let mut vm = VM::new();
-            vm.stack = new_stack;
-            if let Ok(val) = vm.run() {
-                return format!("Fixed - {}", val);
+        }
+        while let Some(addr) = queue.pop_front() {
+            for &prev in &predecessors[addr] {
+                if !terminates[prev] {
+                    terminates[prev] = true;
+                    queue.push_back(prev);
+                }
             }
         }
-        "No solution".to_string()
+
+        // The fix is the first instruction on the path from the start whose flipped successor terminates
+        let mut visited = vec![false; n];
+        let mut addr = 0;
+        while addr < n && !visited[addr] {
+            visited[addr] = true;
+            let ins = self.stack[addr];
+            if let Some(flipped) = ins.flipped() {
+                let fixed = self.successor(addr, flipped).is_none_or(|next| terminates[next]);
+                if fixed {
+                    let mut stack = self.stack.clone();
+                    stack[addr] = flipped;
+                    let mut vm = VM::new();
+                    vm.load_instructions(stack);
+                    let accumulator = vm.run().ok()?;
+                    return Some(Repair { address: addr, original: ins, accumulator });
+                }
+            }
+            match self.successor(addr, ins) {
+                Some(next) => addr = next,
+                None => break,
+            }
+        }
+        None
     }
 
     fn get_next(&mut self) -> Result<Option<Instruction>, ()> {
-        let s = match self.stack.get(self.pointer) {
-            Some(s) => s.clone(),
+        let s = match self.stack.get(self.pointer()) {
+            Some(s) => *s,
             None => return Ok(None),
         };
-        if self.marker[self.pointer] {
+        if self.marker[self.pointer()] > 0 {
             return Err(());
         }
         Ok(Some(s))
     }
 
-    fn execute(&mut self, inst: Instruction) {
-        self.marker[self.pointer] = true;
-        match inst {
-            Instruction::Acc(val) => {
-                self.accumulator += val;
-                self.pointer += 1
+    /// Executes the instruction at the pointer, counting the visit
+    fn execute(&mut self) -> Option<Exit> {
+        let addr = self.pointer();
+        self.marker[addr] += 1;
+        self.machine.step()
+    }
+}
+
+/// One executed instruction, with the accumulator after it has run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pointer: usize,
+    pub instruction: Instruction,
+    pub accumulator: i32,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}\t{}\t{}", self.pointer, self.instruction, self.accumulator)
+    }
+}
+
+/// Where an infinite loop was detected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopReport {
+    pub accumulator: i32,
+    pub cycle: Vec<usize>,
+    pub reentry: TraceEntry,
+}
+
+impl Display for LoopReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Infinite loop with acc={}", self.accumulator)?;
+        writeln!(f, "`{}` at {:04} re-entered {:04}", self.reentry.instruction, self.reentry.pointer, self.cycle[0])?;
+        write!(f, "Cycle of {} instructions: {}", self.cycle.len(), self.cycle.iter().map(|a| format!("{:04}", a)).join(" -> "))
+    }
+}
+
+/// Why a traced run did not terminate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stopped {
+    Loop(LoopReport),
+    Fault(usize),
+}
+
+impl Display for Stopped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stopped::Loop(report) => report.fmt(f),
+            Stopped::Fault(addr) => write!(f, "Fault at {:04}: acc overflowed", addr),
+        }
+    }
+}
+
+/// Every step of a program run, and how the run ended
+pub struct Trace {
+    pub steps: Vec<TraceEntry>,
+    pub outcome: Result<i32, Stopped>,
+}
+
+impl Trace {
+    pub fn write_to(&self, file: &str) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(file)?);
+        writeln!(out, "pointer\tinstruction\taccumulator")?;
+        for step in &self.steps {
+            writeln!(out, "{}", step)?;
+        }
+        out.flush()
+    }
+}
+
+/// Runs `program` from the start, recording every step
+pub fn trace(program: Vec<Instruction>) -> Trace {
+    let mut vm = VM::new();
+    vm.load_instructions(program);
+    vm.run_traced()
+}
+
+/// Traces the program in `file`, reports how it ended, and optionally writes the trace to `out`
+pub fn day8trace(args: &[String]) -> String {
+    let (file, out) = match args {
+        [f] => (f.as_str(), None),
+        [f, out] => (f.as_str(), Some(out)),
+        _ => return "Usage: day8trace <file> [trace file]".to_string(),
+    };
+    let result = match load_program(file) {
+        Ok(program) => trace(program),
+        Err(e) => return e,
+    };
+    if let Some(out) = out {
+        if let Err(e) = result.write_to(out) {
+            return format!("Could not write trace to {}: {}", out, e);
+        }
+    }
+    match result.outcome {
+        Ok(acc) => format!("Terminated after {} steps with acc={}", result.steps.len(), acc),
+        Err(Stopped::Loop(report)) => format!("{} steps before looping\n{}", result.steps.len(), report),
+        Err(fault) => format!("{} steps before stopping\n{}", result.steps.len(), fault),
+    }
+}
+
+/// Why the debugger handed control back to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    /// The next instruction has already been executed, so the program will loop forever
+    Loop(usize),
+    Halted,
+    Fault(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Address(usize),
+    Kind(String),
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Address(addr) => write!(f, "address {:04}", addr),
+            Breakpoint::Kind(kind) => write!(f, "every {}", kind),
+        }
+    }
+}
+
+const DEBUGGER_HELP: &str = "\
+Commands:
+  b <addr|acc|jmp|nop>   set a breakpoint on an address or instruction kind
+  d <n>                  delete breakpoint n
+  bl                     list breakpoints
+  s [n]                  step n instructions (default 1)
+  c                      continue until a breakpoint, a loop or the program halts
+  p                      print the pointer, accumulator and next instruction
+  set acc|ptr <value>    change the accumulator or pointer
+  l [n]                  list n instructions around the pointer (default 5)
+  v                      show how often each instruction has been visited
+  r                      restart the program
+  q                      quit";
+
+/// A stepping debugger for the handheld VM
+pub struct Debugger {
+    vm: VM,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        let mut vm = VM::new();
+        vm.load_instructions(program);
+        vm.reset();
+        Self { vm, breakpoints: Vec::new() }
+    }
+
+    pub fn accumulator(&self) -> i32 {
+        self.vm.accumulator()
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.vm.pointer()
+    }
+
+    pub fn set_accumulator(&mut self, val: i32) {
+        self.vm.machine.memory.set("acc", val);
+    }
+
+    pub fn set_pointer(&mut self, addr: usize) {
+        self.vm.machine.set_pointer(addr);
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) {
+        self.breakpoints.push(bp);
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        if index < self.breakpoints.len() { Some(self.breakpoints.remove(index)) } else { None }
+    }
+
+    pub fn restart(&mut self) {
+        self.vm.reset();
+    }
+
+    /// The number of times each instruction has been executed since the last restart
+    pub fn visits(&self) -> &[usize] {
+        &self.vm.marker
+    }
+
+    fn next_instruction(&self) -> Option<Instruction> {
+        self.vm.stack.get(self.vm.pointer()).copied()
+    }
+
+    fn breakpoint_at(&self, addr: usize) -> bool {
+        let ins = self.vm.stack.get(addr);
+        self.breakpoints.iter().any(|bp| match (bp, ins) {
+            (Breakpoint::Address(a), _) => *a == addr,
+            (Breakpoint::Kind(k), Some(ins)) => k == ins.mnemonic(),
+            (Breakpoint::Kind(_), None) => false,
+        })
+    }
+
+    /// Execute at most `n` instructions. Unlike `cont`, this will happily walk into a loop.
+    pub fn step(&mut self, n: usize) -> Stop {
+        for _ in 0..n {
+            let addr = self.vm.pointer();
+            if self.next_instruction().is_none() {
+                return Stop::Halted;
+            }
+            if let Some(Exit::Fault { .. }) = self.vm.execute() {
+                return Stop::Fault(addr);
+            }
+        }
+        match self.next_instruction() {
+            Some(_) => Stop::Stepped,
+            None => Stop::Halted,
+        }
+    }
+
+    /// Run until the next instruction is on a breakpoint or has already been visited, or the program halts
+    pub fn cont(&mut self) -> Stop {
+        // Always execute the current instruction, so that continuing after a stop makes progress
+        match self.step(1) {
+            Stop::Halted => return Stop::Halted,
+            Stop::Fault(addr) => return Stop::Fault(addr),
+            _ => {}
+        }
+        loop {
+            let addr = self.vm.pointer();
+            if self.breakpoint_at(addr) {
+                return Stop::Breakpoint(addr);
+            }
+            if self.vm.marker[addr] > 0 {
+                return Stop::Loop(addr);
+            }
+            match self.step(1) {
+                Stop::Halted => return Stop::Halted,
+                Stop::Fault(addr) => return Stop::Fault(addr),
+                _ => {}
             }
-            Instruction::Jmp(val) => self.pointer = (self.pointer as i32 + val) as usize,
-            Instruction::Nop(_) => self.pointer += 1,
         }
     }
+
+    pub fn state(&self) -> String {
+        let next = match self.next_instruction() {
+            Some(ins) => ins.to_string(),
+            None => "<halted>".to_string(),
+        };
+        format!("ptr={:04} acc={} next: {}", self.vm.pointer(), self.vm.accumulator(), next)
+    }
+
+    /// The instructions within `radius` of the pointer, with their visit counts
+    pub fn listing(&self, radius: usize) -> String {
+        let start = self.vm.pointer().saturating_sub(radius);
+        let end = self.vm.pointer().saturating_add(radius + 1).min(self.vm.stack.len());
+        (start..end)
+            .map(|addr| {
+                let here = if addr == self.vm.pointer() { ">" } else { " " };
+                let bp = if self.breakpoint_at(addr) { "*" } else { " " };
+                format!("{}{} {:04}  {:<10} visits: {}", here, bp, addr, self.vm.stack[addr].to_string(), self.vm.marker[addr])
+            })
+            .join("\n")
+    }
+
+    pub fn visit_report(&self) -> String {
+        let visited = self.vm.marker.iter()
+            .enumerate()
+            .filter(|(_, &n)| n > 0)
+            .map(|(addr, n)| format!("{:04}  {:<10} x{}", addr, self.vm.stack[addr].to_string(), n))
+            .collect::<Vec<String>>();
+        if visited.is_empty() {
+            "No instructions have been executed".to_string()
+        } else {
+            format!("{} of {} instructions visited\n{}", visited.len(), self.vm.stack.len(), visited.join("\n"))
+        }
+    }
+
+    fn describe(&self, stop: Stop) -> String {
+        let reason = match stop {
+            Stop::Stepped => String::new(),
+            Stop::Breakpoint(addr) => format!("Breakpoint at {:04}\n", addr),
+            Stop::Loop(addr) => format!("Infinite loop: {:04} is about to run a second time\n", addr),
+            Stop::Halted => format!("Program halted with acc={}\n", self.vm.accumulator()),
+            Stop::Fault(addr) => format!("Fault at {:04}: acc overflowed\n", addr),
+        };
+        format!("{}{}", reason, self.state())
+    }
+
+    /// Carry out a single debugger command, returning the text to show the user, or `None` if the user wants to quit
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let reply = match words.as_slice() {
+            [] => String::new(),
+            ["q"] | ["quit"] => return None,
+            ["h"] | ["help"] => DEBUGGER_HELP.to_string(),
+            ["b", target] | ["break", target] => {
+                let bp = match target.parse::<usize>() {
+                    Ok(addr) => Breakpoint::Address(addr),
+                    Err(_) if ["acc", "jmp", "nop"].contains(target) => Breakpoint::Kind(target.to_string()),
+                    Err(_) => return Some(format!("'{}' is not an address or instruction kind", target)),
+                };
+                let reply = format!("Breakpoint {} on {}", self.breakpoints.len(), bp);
+                self.add_breakpoint(bp);
+                reply
+            }
+            ["d", n] | ["delete", n] => match n.parse::<usize>().ok().and_then(|i| self.remove_breakpoint(i)) {
+                Some(bp) => format!("Removed breakpoint on {}", bp),
+                None => format!("There is no breakpoint {}", n),
+            },
+            ["bl"] if self.breakpoints.is_empty() => "No breakpoints".to_string(),
+            ["bl"] => self.breakpoints.iter().enumerate().map(|(i, bp)| format!("{}: {}", i, bp)).join("\n"),
+            ["s"] | ["step"] => self.describe_step(1),
+            ["s", n] | ["step", n] => match n.parse::<usize>() {
+                Ok(n) => self.describe_step(n),
+                Err(_) => format!("'{}' is not a number", n),
+            },
+            ["c"] | ["continue"] => {
+                let stop = self.cont();
+                self.describe(stop)
+            }
+            ["p"] | ["print"] => self.state(),
+            ["set", "acc", v] => match v.parse::<i32>() {
+                Ok(v) => { self.set_accumulator(v); self.state() }
+                Err(_) => format!("'{}' is not a number", v),
+            },
+            ["set", "ptr", v] => match v.parse::<usize>() {
+                Ok(v) => { self.set_pointer(v); self.state() }
+                Err(_) => format!("'{}' is not an address", v),
+            },
+            ["l"] | ["list"] => self.listing(5),
+            ["l", n] | ["list", n] => match n.parse::<usize>() {
+                Ok(n) => self.listing(n),
+                Err(_) => format!("'{}' is not a number", n),
+            },
+            ["v"] | ["visits"] => self.visit_report(),
+            ["r"] | ["restart"] => { self.restart(); self.state() }
+            _ => format!("Unknown command '{}'. Type 'h' for help.", line.trim()),
+        };
+        Some(reply)
+    }
+
+    fn describe_step(&mut self, n: usize) -> String {
+        let stop = self.step(n);
+        self.describe(stop)
+    }
 }
 
-fn read_data() -> Vec<String> {
-    fs::read_to_string("assets/day8.txt")
-        .expect("Could not read file")
-        .lines()
-        .filter(|&s| !s.is_empty())
-        .map(String::from)
-        .collect()
-}
\ No newline at end of file
+/// Runs the debugger on the program in `file`, reading commands from stdin
+pub fn day8debug(args: &[String]) -> String {
+    let file = match args {
+        [f] => f.as_str(),
+        _ => return "Usage: debug <file>".to_string(),
+    };
+    let program = match load_program(file) {
+        Ok(p) => p,
+        Err(e) => return e,
+    };
+    let mut debugger = Debugger::new(program);
+    println!("Loaded {} instructions from {}. Type 'h' for help.", debugger.vm.stack.len(), file);
+    println!("{}", debugger.state());
+    let stdin = io::stdin();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().expect("Could not write to stdout");
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match debugger.command(&line) {
+            Some(reply) if reply.is_empty() => {}
+            Some(reply) => println!("{}", reply),
+            None => break,
+        }
+    }
+    debugger.state()
+}
+
+/// Runs the program in `file` on the extended instruction set and reports the final registers
+pub fn day8run(args: &[String]) -> String {
+    let usage = "Usage: day8run <file> [max steps]";
+    let file = match args.first() {
+        Some(f) => f.as_str(),
+        None => return usage.to_string(),
+    };
+    let max_steps = match args.get(1).map(|s| s.parse::<usize>()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => return usage.to_string(),
+        None => MAX_STEPS,
+    };
+    let src = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return format!("Could not read {}: {}", file, e),
+    };
+    let program = match InstructionSet::extended().assemble(&src) {
+        Ok(p) => p,
+        Err(e) => return format!("Could not assemble {}: {}", file, e),
+    };
+    let mut machine = Machine::new(program).with_step_limit(max_steps);
+    let exit = machine.run();
+    if machine.memory.is_empty() {
+        format!("{} - no registers were written", exit)
+    } else {
+        format!("{} - {}", exit, machine.memory)
+    }
+}
+
+pub fn load_program(file: &str) -> Result<Vec<Instruction>, String> {
+    let src = fs::read_to_string(file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+    assemble(&src).map_err(|e| format!("Could not assemble {}: {}", file, e))
+}
+
+fn read_data() -> Vec<Instruction> {
+    load_program("assets/day8.txt").unwrap()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(program: &[Instruction]) {
+        let text = disassemble(program);
+        assert_eq!(assemble(&text).as_deref(), Ok(program), "{}", text);
+        // Disassembling the reassembled program must also give the same text back
+        assert_eq!(disassemble(&assemble(&text).unwrap()), text);
+    }
+
+    #[test]
+    fn disassembly_round_trips() {
+        use Instruction::*;
+        let programs = vec![
+            vec![],
+            vec![Acc(0)],
+            vec![Nop(0), Acc(1), Jmp(4), Acc(3), Jmp(-3), Acc(-99), Acc(1), Jmp(-4), Acc(6)],
+            // Jumps to the address just past the end, out of the program in both directions, and onto themselves
+            vec![Jmp(2), Acc(-1), Nop(-1), Jmp(0)],
+            vec![Jmp(1000), Nop(-1000), Jmp(-1), Acc(i32::MAX), Acc(i32::MIN)],
+            read_data(),
+        ];
+        for program in &programs {
+            round_trip(program);
+        }
+    }
+
+    /// Runs `program` with the instruction at `repair.address` flipped
+    fn run_repaired(program: &[Instruction], repair: &Repair) -> Result<i32, i32> {
+        let mut fixed = program.to_vec();
+        assert_eq!(fixed[repair.address], repair.original);
+        fixed[repair.address] = repair.original.flipped().unwrap();
+        let mut vm = VM::new();
+        vm.load_instructions(fixed);
+        vm.run()
+    }
+
+    #[test]
+    fn repair_agrees_with_brute_force() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use Instruction::*;
+        let mut programs = vec![
+            vec![Nop(0), Acc(1), Jmp(4), Acc(3), Jmp(-3), Acc(-99), Acc(1), Jmp(-4), Acc(6)],
+            vec![Jmp(0)],
+            vec![Acc(1), Nop(1), Jmp(-2)],
+            read_data(),
+        ];
+        let mut rng = StdRng::seed_from_u64(8);
+        while programs.len() < 500 {
+            let len = rng.gen_range(1..20);
+            let program = (0..len)
+                .map(|_| {
+                    let arg = rng.gen_range(-5..=5);
+                    match rng.gen_range(0..3) {
+                        0 => Acc(arg),
+                        1 => Jmp(arg),
+                        _ => Nop(arg),
+                    }
+                })
+                .collect::<Vec<Instruction>>();
+            programs.push(program);
+        }
+
+        let mut repaired = 0;
+        for program in programs {
+            let mut vm = VM::new();
+            vm.load_instructions(program.clone());
+            if vm.run().is_ok() {
+                continue;
+            }
+            let fast = vm.repair();
+            let slow = vm.repair_brute_force();
+            assert_eq!(fast.is_some(), slow.is_some(), "{:?}", program);
+            for fix in fast.iter().chain(slow.iter()) {
+                assert_eq!(run_repaired(&program, fix), Ok(fix.accumulator), "{:?} {:?}", program, fix);
+            }
+            if fast.is_some() {
+                repaired += 1;
+            }
+        }
+        assert!(repaired > 10);
+    }
+
+    #[test]
+    fn labels_resolve_to_offsets() {
+        use Instruction::*;
+        let src = "\
+            start: acc +1   # comment
+            jmp end         ; another
+            nop start
+            end: back: jmp back
+        ";
+        let program = assemble(src).unwrap();
+        assert_eq!(program, vec![Acc(1), Jmp(2), Nop(-2), Jmp(0)]);
+        round_trip(&program);
+    }
+}
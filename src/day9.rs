@@ -1,9 +1,11 @@
-use itertools::Itertools;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
+use std::io::{self, BufRead};
+use std::ops::Range;
 
 pub fn day9a() -> String {
     let data = read_data();
-    match check_xmas(&data, 25) {
+    match XmasValidator::new(25).and_then(|v| v.first_invalid(&data)) {
         None => "No solution".to_string(),
         Some(v) => v.to_string(),
     }
@@ -11,31 +13,133 @@ pub fn day9a() -> String {
 
 pub fn day9b() -> String {
     let data = read_data();
-    if let Some(val) = check_xmas(&data, 25) {
-        let n = data.len();
-        for i in 0..n {
-            let mut tot = 0usize;
-            let mut offset = 1;
-            while tot < val && i+offset < n {
-                tot += data[i + offset];
-                offset += 1;
-            }
-            if tot == val {
-                let min = data[i..i+offset].iter().copied().min().unwrap();
-                let max = data[i..i+offset].iter().copied().max().unwrap();
-                return (min + max).to_string()
+    let range = XmasValidator::new(25)
+        .and_then(|v| v.first_invalid(&data))
+        .and_then(|val| find_contiguous_sum(&data, val));
+    match range {
+        Some(range) => {
+            let min = data[range.clone()].iter().min().unwrap();
+            let max = data[range].iter().max().unwrap();
+            (min + max).to_string()
+        }
+        None => "No solution".to_string(),
+    }
+}
+
+/// Reads numbers from stdin, one per line, and prints every invalid number as soon as it arrives
+pub fn day9stream(args: &[String]) -> String {
+    let usage = "Usage: day9stream [preamble of at least 2]".to_string();
+    let preamble = match args {
+        [] => 25,
+        [p] => match p.parse::<usize>() {
+            Ok(p) => p,
+            Err(_) => return usage,
+        },
+        _ => return usage,
+    };
+    let mut validator = match XmasValidator::new(preamble) {
+        Some(v) => v,
+        None => return usage,
+    };
+    let mut invalid = 0;
+    for (i, line) in io::stdin().lock().lines().enumerate() {
+        let line = line.expect("Could not read from stdin");
+        let val = match line.trim().parse::<usize>() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if validator.push(val) == Check::Invalid {
+            println!("Line {}: {} is not the sum of two of the previous {} numbers", i + 1, val, preamble);
+            invalid += 1;
+        }
+    }
+    format!("{} invalid numbers", invalid)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Preamble,
+    Valid,
+    Invalid,
+}
+
+/// Checks that every number after the preamble is the sum of two of the `preamble` numbers before it
+pub struct XmasValidator {
+    preamble: usize,
+    window: VecDeque<usize>,
+    // How many pairs in the window add up to each sum
+    sums: HashMap<usize, usize>,
+}
+
+impl XmasValidator {
+    /// A validator for the given preamble length, which must be at least 2 to have a pair to sum
+    pub fn new(preamble: usize) -> Option<Self> {
+        if preamble < 2 {
+            return None;
+        }
+        Some(Self {
+            preamble,
+            window: VecDeque::with_capacity(preamble),
+            sums: HashMap::new(),
+        })
+    }
+
+    /// Checks `val` against the current window, then adds it to the window
+    pub fn push(&mut self, val: usize) -> Check {
+        let check = if self.window.len() < self.preamble {
+            Check::Preamble
+        } else if self.sums.contains_key(&val) {
+            Check::Valid
+        } else {
+            Check::Invalid
+        };
+        if self.window.len() == self.preamble {
+            let old = self.window.pop_front().unwrap();
+            for w in &self.window {
+                let sum = old + w;
+                let count = self.sums.get_mut(&sum).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.sums.remove(&sum);
+                }
             }
         }
+        for w in &self.window {
+            *self.sums.entry(val + w).or_insert(0) += 1;
+        }
+        self.window.push_back(val);
+        check
+    }
+
+    /// The position and value of every invalid number in `data`
+    pub fn find_invalid(mut self, data: &[usize]) -> Vec<(usize, usize)> {
+        data.iter()
+            .enumerate()
+            .filter(|(_, &val)| self.push(val) == Check::Invalid)
+            .map(|(i, &val)| (i, val))
+            .collect()
+    }
+
+    pub fn first_invalid(mut self, data: &[usize]) -> Option<usize> {
+        data.iter().copied().find(|&val| self.push(val) == Check::Invalid)
     }
-    "No solution".to_string()
 }
 
-fn check_xmas(data: &[usize], preamble: usize) -> Option<usize> {
-    data.windows(preamble + 1).find(|&window| {
-        let target = window[preamble];
-        let win = &window[0..preamble];
-        !win.iter().combinations(2).any(|v| v.iter().copied().sum::<usize>() == target)
-    }).map(|w| w[preamble])
+/// Finds a run of at least two numbers in `data` that add up to `target`, in a single pass
+pub fn find_contiguous_sum(data: &[usize], target: usize) -> Option<Range<usize>> {
+    let mut start = 0;
+    let mut sum = 0;
+    for (end, &val) in data.iter().enumerate() {
+        sum += val;
+        while sum > target {
+            sum -= data[start];
+            start += 1;
+        }
+        if sum == target && end > start {
+            return Some(start..end + 1);
+        }
+    }
+    None
 }
 
 fn read_data() -> Vec<usize> {
@@ -44,4 +148,25 @@ fn read_data() -> Vec<usize> {
         .lines()
         .filter_map(|s| s.parse::<usize>().ok())
         .collect::<Vec<usize>>()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preamble_must_allow_a_pair() {
+        assert!(XmasValidator::new(0).is_none());
+        assert!(XmasValidator::new(1).is_none());
+        let validator = XmasValidator::new(2).unwrap();
+        assert_eq!(validator.find_invalid(&[1, 2, 3, 5, 9, 14]), vec![(4, 9)]);
+    }
+
+    #[test]
+    fn finds_the_example_weakness() {
+        let data = [35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576];
+        let invalid = XmasValidator::new(5).unwrap().first_invalid(&data);
+        assert_eq!(invalid, Some(127));
+        assert_eq!(find_contiguous_sum(&data, 127), Some(2..6));
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use itertools::Itertools;
+
+/// The registers of the machine, which read as zero until they are first written
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Memory {
+    registers: BTreeMap<String, i32>,
+}
+
+impl Memory {
+    pub fn get(&self, register: &str) -> i32 {
+        self.registers.get(register).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, register: &str, val: i32) {
+        self.registers.insert(register.to_string(), val);
+    }
+
+    pub fn registers(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.registers.iter().map(|(k, v)| (k.as_str(), *v))
+    }
+
+    /// Whether no register has been written yet
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+    }
+}
+
+impl Display for Memory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.registers().map(|(k, v)| format!("{}={}", k, v)).join(" "))
+    }
+}
+
+/// What an operand of an instruction may hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Register,
+    Value,
+    Offset,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(String),
+    Number(i32),
+}
+
+impl Operand {
+    pub fn value(&self, memory: &Memory) -> i32 {
+        match self {
+            Operand::Register(r) => memory.get(r),
+            Operand::Number(n) => *n,
+        }
+    }
+
+    /// The register name, which the assembler guarantees for `OperandKind::Register` operands
+    pub fn register(&self) -> &str {
+        match self {
+            Operand::Register(r) => r.as_str(),
+            Operand::Number(_) => unreachable!(),
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Register(r) => f.write_str(r),
+            Operand::Number(n) => write!(f, "{:+}", n),
+        }
+    }
+}
+
+/// Where the pointer goes after an instruction has executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    Jump(i32),
+    Halt,
+    /// The instruction could not be executed, e.g. because a register would overflow
+    Fault(&'static str),
+}
+
+/// The definition of an opcode. Implement this and add it to an `InstructionSet` to extend the machine.
+pub trait OpDef {
+    fn mnemonic(&self) -> &'static str;
+    fn operands(&self) -> &'static [OperandKind];
+    /// Whether the flow returned by `execute` depends on the registers
+    fn is_conditional(&self) -> bool {
+        false
+    }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow;
+}
+
+pub struct Acc;
+pub struct Jmp;
+pub struct Nop;
+pub struct Halt;
+pub struct Mov;
+pub struct Add;
+pub struct Jz;
+pub struct Jnz;
+
+impl OpDef for Acc {
+    fn mnemonic(&self) -> &'static str { "acc" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Value] }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow {
+        match memory.get("acc").checked_add(operands[0].value(memory)) {
+            Some(val) => {
+                memory.set("acc", val);
+                Flow::Next
+            }
+            None => Flow::Fault("acc overflowed"),
+        }
+    }
+}
+
+impl OpDef for Jmp {
+    fn mnemonic(&self) -> &'static str { "jmp" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Offset] }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow {
+        Flow::Jump(operands[0].value(memory))
+    }
+}
+
+impl OpDef for Nop {
+    fn mnemonic(&self) -> &'static str { "nop" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Offset] }
+    fn execute(&self, _operands: &[Operand], _memory: &mut Memory) -> Flow {
+        Flow::Next
+    }
+}
+
+impl OpDef for Halt {
+    fn mnemonic(&self) -> &'static str { "halt" }
+    fn operands(&self) -> &'static [OperandKind] { &[] }
+    fn execute(&self, _operands: &[Operand], _memory: &mut Memory) -> Flow {
+        Flow::Halt
+    }
+}
+
+/// `mov r x` sets register `r` to `x`
+impl OpDef for Mov {
+    fn mnemonic(&self) -> &'static str { "mov" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Register, OperandKind::Value] }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow {
+        memory.set(operands[0].register(), operands[1].value(memory));
+        Flow::Next
+    }
+}
+
+/// `add r x` adds `x` to register `r`
+impl OpDef for Add {
+    fn mnemonic(&self) -> &'static str { "add" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Register, OperandKind::Value] }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow {
+        let r = operands[0].register();
+        match memory.get(r).checked_add(operands[1].value(memory)) {
+            Some(val) => {
+                memory.set(r, val);
+                Flow::Next
+            }
+            None => Flow::Fault("register overflowed"),
+        }
+    }
+}
+
+/// `jz x offset` jumps if `x` is zero
+impl OpDef for Jz {
+    fn mnemonic(&self) -> &'static str { "jz" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Value, OperandKind::Offset] }
+    fn is_conditional(&self) -> bool { true }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow {
+        if operands[0].value(memory) == 0 { Flow::Jump(operands[1].value(memory)) } else { Flow::Next }
+    }
+}
+
+/// `jnz x offset` jumps if `x` is not zero
+impl OpDef for Jnz {
+    fn mnemonic(&self) -> &'static str { "jnz" }
+    fn operands(&self) -> &'static [OperandKind] { &[OperandKind::Value, OperandKind::Offset] }
+    fn is_conditional(&self) -> bool { true }
+    fn execute(&self, operands: &[Operand], memory: &mut Memory) -> Flow {
+        if operands[0].value(memory) != 0 { Flow::Jump(operands[1].value(memory)) } else { Flow::Next }
+    }
+}
+
+/// An assembled instruction: the opcode definition along with its operands
+#[derive(Clone)]
+pub struct Op {
+    def: Rc<dyn OpDef>,
+    operands: Vec<Operand>,
+}
+
+impl Op {
+    pub fn mnemonic(&self) -> &'static str {
+        self.def.mnemonic()
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.def.mnemonic())?;
+        for operand in &self.operands {
+            write!(f, " {}", operand)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    pub(crate) fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Label addresses, and program lines with their line numbers
+pub(crate) type SourceLines<'a> = (HashMap<String, usize>, Vec<(usize, &'a str)>);
+
+/// Strips comments and labels, noting the address each label refers to
+pub(crate) fn split_labels(src: &str) -> Result<SourceLines<'_>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    for (i, raw) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let mut line = raw.split(['#', ';']).next().unwrap_or("").trim();
+        while let Some(pos) = line.find(':') {
+            let label = line[..pos].trim();
+            if !is_label(label) {
+                return Err(AsmError::new(line_no, format!("Invalid label '{}'", label)));
+            }
+            if labels.insert(label.to_string(), lines.len()).is_some() {
+                return Err(AsmError::new(line_no, format!("Duplicate label '{}'", label)));
+            }
+            line = line[pos + 1..].trim();
+        }
+        if !line.is_empty() {
+            lines.push((line_no, line));
+        }
+    }
+    Ok((labels, lines))
+}
+
+pub(crate) fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The opcodes an assembler understands
+#[derive(Default)]
+pub struct InstructionSet {
+    ops: HashMap<&'static str, Rc<dyn OpDef>>,
+}
+
+impl InstructionSet {
+    /// The original `acc`, `jmp` and `nop`
+    pub fn classic() -> Self {
+        let mut set = Self::default();
+        set.add(Acc);
+        set.add(Jmp);
+        set.add(Nop);
+        set
+    }
+
+    /// The classic opcodes plus `halt`, `mov`, `add`, `jz` and `jnz`
+    pub fn extended() -> Self {
+        let mut set = Self::classic();
+        set.add(Halt);
+        set.add(Mov);
+        set.add(Add);
+        set.add(Jz);
+        set.add(Jnz);
+        set
+    }
+
+    /// Adds an opcode, replacing any existing one with the same mnemonic
+    pub fn add<T: OpDef + 'static>(&mut self, def: T) {
+        self.ops.insert(def.mnemonic(), Rc::new(def));
+    }
+
+    /// The opcode `mnemonic` with the given operands, or `None` if the set has no such opcode
+    pub fn op(&self, mnemonic: &str, operands: Vec<Operand>) -> Option<Op> {
+        self.ops.get(mnemonic).map(|def| Op { def: Rc::clone(def), operands })
+    }
+
+    /// Assembles a program written like `day8::assemble` expects, with operands separated by whitespace
+    pub fn assemble(&self, src: &str) -> Result<Vec<Op>, AsmError> {
+        let (labels, lines) = split_labels(src)?;
+        lines.iter()
+            .enumerate()
+            .map(|(addr, &(line_no, line))| {
+                let mut parts = line.split_whitespace();
+                let mnemonic = parts.next().unwrap_or_default();
+                let def = self.ops.get(mnemonic)
+                    .ok_or_else(|| AsmError::new(line_no, format!("Unknown instruction '{}'", mnemonic)))?;
+                let args = parts.collect::<Vec<&str>>();
+                if args.len() != def.operands().len() {
+                    let msg = format!("'{}' takes {} operands, not {}", mnemonic, def.operands().len(), args.len());
+                    return Err(AsmError::new(line_no, msg));
+                }
+                let operands = def.operands().iter()
+                    .zip(args)
+                    .map(|(kind, arg)| match (kind, arg.parse::<i32>()) {
+                        (OperandKind::Register, Ok(_)) => {
+                            Err(AsmError::new(line_no, format!("Expected a register, not '{}'", arg)))
+                        }
+                        (_, Ok(n)) => Ok(Operand::Number(n)),
+                        (OperandKind::Offset, Err(_)) => labels.get(arg)
+                            .map(|&target| Operand::Number(target as i32 - addr as i32))
+                            .ok_or_else(|| AsmError::new(line_no, format!("Unknown label '{}'", arg))),
+                        (_, Err(_)) if is_label(arg) => Ok(Operand::Register(arg.to_string())),
+                        (_, Err(_)) => Err(AsmError::new(line_no, format!("'{}' is not a valid operand", arg))),
+                    })
+                    .collect::<Result<Vec<Operand>, AsmError>>()?;
+                Ok(Op { def: Rc::clone(def), operands })
+            })
+            .collect()
+    }
+}
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Halted,
+    EndOfProgram,
+    Loop(usize),
+    Fault { address: usize, reason: &'static str },
+    StepLimit(usize),
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exit::Halted => f.write_str("Halted"),
+            Exit::EndOfProgram => f.write_str("Ok"),
+            Exit::Loop(addr) => write!(f, "Infinite loop at {:04}", addr),
+            Exit::Fault { address, reason } => write!(f, "Fault at {:04}: {}", address, reason),
+            Exit::StepLimit(steps) => write!(f, "Gave up after {} steps", steps),
+        }
+    }
+}
+
+/// The default for `Machine::with_step_limit`
+pub const MAX_STEPS: usize = 100_000;
+
+pub struct Machine {
+    program: Vec<Op>,
+    pointer: usize,
+    pub memory: Memory,
+    max_steps: usize,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Machine {
+    pub fn new(program: Vec<Op>) -> Self {
+        Self { program, pointer: 0, memory: Memory::default(), max_steps: MAX_STEPS }
+    }
+
+    /// Makes `run` give up after `max_steps` instructions
+    pub fn with_step_limit(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn program(&self) -> &[Op] {
+        &self.program
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    pub fn set_pointer(&mut self, addr: usize) {
+        self.pointer = addr;
+    }
+
+    pub fn reset(&mut self) {
+        self.pointer = 0;
+        self.memory = Memory::default();
+    }
+
+    /// Executes the instruction at the pointer, returning `Some` if the run is over
+    pub fn step(&mut self) -> Option<Exit> {
+        let op = match self.program.get(self.pointer) {
+            Some(op) => op,
+            None => return Some(Exit::EndOfProgram),
+        };
+        match op.def.execute(&op.operands, &mut self.memory) {
+            Flow::Next => self.pointer += 1,
+            Flow::Jump(offset) => {
+                // A jump to before the start leaves the program just like one past the end
+                let target = self.pointer as i64 + offset as i64;
+                self.pointer = if target < 0 { self.program.len() } else { target as usize };
+            }
+            Flow::Halt => return Some(Exit::Halted),
+            Flow::Fault(reason) => return Some(Exit::Fault { address: self.pointer, reason }),
+        }
+        if self.pointer >= self.program.len() { Some(Exit::EndOfProgram) } else { None }
+    }
+
+    /// Runs from the start until the program halts, loops, faults or reaches the step limit
+    pub fn run(&mut self) -> Exit {
+        self.reset();
+        if self.program.is_empty() {
+            return Exit::EndOfProgram;
+        }
+        // Without conditional instructions, revisiting an address is enough to know we loop
+        let conditional = self.program.iter().any(|op| op.def.is_conditional());
+        let mut visited = vec![false; self.program.len()];
+        let mut seen = HashSet::new();
+        for _ in 0..self.max_steps {
+            let looped = if conditional {
+                !seen.insert((self.pointer, self.memory.clone()))
+            } else {
+                std::mem::replace(&mut visited[self.pointer], true)
+            };
+            if looped {
+                return Exit::Loop(self.pointer);
+            }
+            if let Some(exit) = self.step() {
+                return exit;
+            }
+        }
+        Exit::StepLimit(self.max_steps)
+    }
+}
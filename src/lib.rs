@@ -2,6 +2,9 @@
 #![feature(linked_list_prepend)]
 
 pub mod bits;
+pub mod handheld;
+pub mod number_theory;
+pub mod render;
 // pub mod backtracker;
 pub mod day1;
 pub mod day2;
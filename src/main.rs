@@ -1,20 +1,20 @@
-use aoc2020::day1::{day1a, day1b};
-use aoc2020::day10::{day10a, day10b};
-use aoc2020::day11::{day11a, day11b};
-use aoc2020::day2::{day2a, day2b};
-use aoc2020::day3::{day3a, day3b};
-use aoc2020::day4::{day4a, day4b};
-use aoc2020::day5::{day5a, day5b};
-use aoc2020::day6::{day6a, day6b};
-use aoc2020::day7::{day7a, day7b};
-use aoc2020::day8::{day8a, day8b};
-use aoc2020::day9::{day9a, day9b};
-use aoc2020::day12::{day12a, day12b};
-use aoc2020::day13::{day13a, day13b};
+use aoc2020::day1::{day1a, day1b, day1ksum};
+use aoc2020::day10::{day10a, day10b, day10chains, day10count};
+use aoc2020::day11::{day11a, day11animate, day11b, day11rules};
+use aoc2020::day2::{day2a, day2b, day2report};
+use aoc2020::day3::{day3a, day3b, day3survey};
+use aoc2020::day4::{day4a, day4b, day4validate};
+use aoc2020::day5::{day5a, day5b, day5encode, day5map};
+use aoc2020::day6::{day6a, day6b, day6stats};
+use aoc2020::day7::{day7a, day7b, day7export};
+use aoc2020::day8::{day8a, day8b, day8debug, day8run, day8trace};
+use aoc2020::day9::{day9a, day9b, day9stream};
+use aoc2020::day12::{day12a, day12b, day12route};
+use aoc2020::day13::{day13a, day13b, day13schedule};
 use aoc2020::day14::{day14a, day14b};
 use aoc2020::day15::{day15a, day15b};
 use aoc2020::day16::{day16a, day16b};
-use aoc2020::day17::{day17a, day17b};
+use aoc2020::day17::{day17a, day17animate, day17b};
 use aoc2020::day18::{day18a, day18b};
 use aoc2020::day19::{day19a, day19b};
 use aoc2020::day20::{day20a, day20b};
@@ -30,30 +30,48 @@ fn main() {
     let result = match problem {
         "day1a" => day1a(),
         "day1b" => day1b(),
+        "day1ksum" => day1ksum(&args[2..]),
         "day2a" => day2a(),
         "day2b" => day2b(),
+        "day2report" => day2report(&args[2..]),
         "day3a" => day3a(),
         "day3b" => day3b(),
+        "day3survey" => day3survey(&args[2..]),
         "day4a" => day4a(),
         "day4b" => day4b(),
+        "day4validate" => day4validate(&args[2..]),
         "day5a" => day5a(),
         "day5b" => day5b(),
+        "day5map" => day5map(),
+        "day5encode" => day5encode(&args[2..]),
         "day6a" => day6a(),
         "day6b" => day6b(),
+        "day6stats" => day6stats(&args[2..]),
         "day7a" => day7a(),
         "day7b" => day7b(),
+        "day7export" => day7export(&args[2..]),
         "day8a" => day8a(),
         "day8b" => day8b(),
+        "day8trace" => day8trace(&args[2..]),
+        "day8run" => day8run(&args[2..]),
+        "debug" => day8debug(&args[2..]),
         "day9a" => day9a(),
         "day9b" => day9b(),
+        "day9stream" => day9stream(&args[2..]),
         "day10a" => day10a(),
         "day10b" => day10b(),
+        "day10count" => day10count(&args[2..]),
+        "day10chains" => day10chains(&args[2..]),
         "day11a" => day11a(),
         "day11b" => day11b(),
+        "day11rules" => day11rules(&args[2..]),
+        "day11animate" => day11animate(&args[2..]),
         "day12a" => day12a(),
         "day12b" => day12b(),
+        "day12route" => day12route(&args[2..]),
         "day13a" => day13a(),
         "day13b" => day13b(),
+        "day13schedule" => day13schedule(&args[2..]),
         "day14a" => day14a(),
         "day14b" => day14b(),
         "day15a" => day15a(),
@@ -62,6 +80,7 @@ fn main() {
         "day16b" => day16b(),
         "day17a" => day17a(),
         "day17b" => day17b(),
+        "day17animate" => day17animate(&args[2..]),
         "day18a" => day18a(),
         "day18b" => day18b(),
         "day19a" => day19a(),
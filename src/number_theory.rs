@@ -0,0 +1,158 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Extended Euclid: returns `(g, x, y)` with `g = gcd(a, b) >= 0` and `a*x + b*y = g`
+#[allow(clippy::many_single_char_names)]
+pub fn egcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (x0, x1) = (x1, x0 - q * x1);
+        (y0, y1) = (y1, y0 - q * y1);
+    }
+    if r0 < 0 {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+pub fn gcd(a: i128, b: i128) -> i128 {
+    egcd(a, b).0
+}
+
+/// The inverse of `x` modulo `n`, in `0..n`, if `x` and `n` are coprime
+pub fn mod_inv(x: i128, n: i128) -> Option<i128> {
+    if n <= 0 {
+        return None;
+    }
+    let (g, x, _) = egcd(x.rem_euclid(n), n);
+    if g == 1 {
+        Some(x.rem_euclid(n))
+    } else {
+        None
+    }
+}
+
+/// `a * b mod m` for `a` and `b` in `0..m`, without overflowing
+pub fn mul_mod(a: i128, b: i128, m: i128) -> i128 {
+    if let Some(p) = a.checked_mul(b) {
+        return p % m;
+    }
+    // Additions are done as subtractions from m so that they can't overflow either
+    let add = |x: i128, y: i128| if x >= m - y { x - (m - y) } else { x + y };
+    let (mut a, mut b) = (a, b);
+    let mut result = 0;
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add(result, a);
+        }
+        a = add(a, a);
+        b >>= 1;
+    }
+    result
+}
+
+/// `base^exp mod modulus` by repeated squaring
+pub fn mod_pow(base: u64, exp: u64, modulus: u64) -> u64 {
+    if modulus == 1 {
+        return 0;
+    }
+    let m = modulus as u128;
+    let mut base = base as u128 % m;
+    let mut exp = exp;
+    let mut result = 1u128;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % m;
+        }
+        base = base * base % m;
+        exp >>= 1;
+    }
+    result as u64
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CrtError {
+    InvalidModulus(i128),
+    Inconsistent { a: i128, m: i128, b: i128, n: i128 },
+    Overflow,
+}
+
+impl Display for CrtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CrtError::InvalidModulus(m) => write!(f, "Invalid modulus {}", m),
+            CrtError::Inconsistent { a, m, b, n } => {
+                write!(f, "x = {} mod {} and x = {} mod {} have no common solution", a, m, b, n)
+            }
+            CrtError::Overflow => write!(f, "The combined modulus is too large"),
+        }
+    }
+}
+
+/// Solves `x = residue mod modulus` for every pair, returning the smallest solution and the lcm of the moduli
+pub fn crt(congruences: &[(i128, i128)]) -> Result<(i128, i128), CrtError> {
+    congruences.iter().try_fold((0, 1), |(a, m), &(b, n)| {
+        if n <= 0 {
+            return Err(CrtError::InvalidModulus(n));
+        }
+        let b = b.rem_euclid(n);
+        let g = gcd(m, n);
+        if (b - a).rem_euclid(g) != 0 {
+            return Err(CrtError::Inconsistent { a, m, b, n });
+        }
+        let lcm = (m / g).checked_mul(n).ok_or(CrtError::Overflow)?;
+        // x = a + m*k, where m*k = b - a mod n, so (m/g)*k = (b-a)/g mod n/g
+        let n_g = n / g;
+        let diff = ((b - a) / g).rem_euclid(n_g);
+        let k = mul_mod(diff, mod_inv(m / g, n_g).expect("m/g and n/g are coprime"), n_g);
+        Ok(((a + m * k).rem_euclid(lcm), lcm))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks every candidate up to the lcm of the moduli
+    fn brute_crt(congruences: &[(i128, i128)]) -> Option<(i128, i128)> {
+        let lcm = congruences.iter().fold(1, |l, &(_, n)| l / gcd(l, n) * n);
+        (0..lcm)
+            .find(|x| congruences.iter().all(|&(b, n)| (x - b).rem_euclid(n) == 0))
+            .map(|x| (x, lcm))
+    }
+
+    #[test]
+    fn crt_agrees_with_brute_force() {
+        for m in 1..=12 {
+            for n in 1..=12 {
+                for a in -3..m {
+                    for b in 0..n {
+                        let congruences = [(a, m), (b, n)];
+                        assert_eq!(crt(&congruences).ok(), brute_crt(&congruences), "{:?}", congruences);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crt_handles_shared_factors() {
+        assert_eq!(crt(&[(2, 4), (4, 6)]), Ok((10, 12)));
+        assert_eq!(crt(&[(1, 4), (2, 6)]), Err(CrtError::Inconsistent { a: 1, m: 4, b: 2, n: 6 }));
+        assert_eq!(crt(&[(0, 0)]), Err(CrtError::InvalidModulus(0)));
+    }
+
+    #[test]
+    fn crt_reports_overflow() {
+        let big = 1i128 << 100;
+        assert_eq!(crt(&[(1, big - 1), (2, big + 1)]), Err(CrtError::Overflow));
+        // Large coprime moduli whose product still fits
+        let (m, n) = ((1i128 << 62) - 1, (1i128 << 62) + 1);
+        let (x, lcm) = crt(&[(5, m), (7, n)]).unwrap();
+        assert_eq!((lcm, x % m, x % n), (m * n, 5, 7));
+    }
+}
@@ -0,0 +1,198 @@
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// A cell of a cellular automaton, e.g. floor, an empty seat, or an occupied seat
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Blank,
+    Empty,
+    Full,
+}
+
+impl Cell {
+    pub fn symbol(&self) -> char {
+        match self {
+            Cell::Blank => '.',
+            Cell::Empty => 'L',
+            Cell::Full => '#',
+        }
+    }
+
+    pub fn colour(&self) -> [u8; 3] {
+        PALETTE[self.palette_index()]
+    }
+
+    fn palette_index(&self) -> usize {
+        match self {
+            Cell::Blank => 0,
+            Cell::Empty => 1,
+            Cell::Full => 2,
+        }
+    }
+}
+
+const PALETTE: [[u8; 3]; 3] = [[32, 32, 32], [80, 130, 80], [230, 200, 60]];
+
+/// One generation of a simulation, as a grid of cells
+pub trait Frame {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn cell(&self, x: usize, y: usize) -> Cell;
+
+    fn text(&self) -> String {
+        (0..self.height())
+            .map(|y| (0..self.width()).map(|x| self.cell(x, y).symbol()).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The frame as RGB pixels, with every cell drawn as a `scale` x `scale` square
+    fn pixels(&self, scale: usize) -> Vec<u8> {
+        self.palette_indices(scale).iter().flat_map(|&i| PALETTE[i as usize]).collect()
+    }
+
+    fn palette_indices(&self, scale: usize) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.width() * self.height() * scale * scale);
+        for y in 0..self.height() * scale {
+            for x in 0..self.width() * scale {
+                result.push(self.cell(x / scale, y / scale).palette_index() as u8);
+            }
+        }
+        result
+    }
+}
+
+/// Where the frames go: the terminal, numbered PPM or PNG files in a directory, or one animated GIF
+pub enum Output {
+    Terminal(Duration),
+    Ppm(PathBuf),
+    Png(PathBuf),
+    Gif(PathBuf, Duration),
+}
+
+/// Writes each generation of a simulation as a frame of an animation
+pub struct Animator {
+    output: Output,
+    scale: usize,
+    frames: usize,
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+    last_height: usize,
+}
+
+const ANIMATOR_USAGE: &str = "--terminal [delay ms] | --ppm <dir> | --png <dir> | --gif <file> [delay ms], \
+    optionally with --scale <pixels per cell>";
+
+impl Animator {
+    pub fn new(output: Output, scale: usize) -> Self {
+        Self { output, scale: scale.max(1), frames: 0, gif: None, last_height: 0 }
+    }
+
+    /// Picks the animator options out of `args`, returning the animator and the remaining arguments
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>), String> {
+        let mut output = None;
+        let mut scale = 4;
+        let mut rest = Vec::new();
+        let mut args = args.iter().peekable();
+        let delay = |s: Option<&&String>| s.and_then(|s| s.parse::<u64>().ok()).map(Duration::from_millis);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--terminal" => {
+                    let d = delay(args.peek());
+                    if d.is_some() {
+                        args.next();
+                    }
+                    output = Some(Output::Terminal(d.unwrap_or(Duration::from_millis(200))));
+                }
+                "--ppm" | "--png" | "--gif" => {
+                    let path = PathBuf::from(args.next().ok_or_else(|| format!("{} needs a path", arg))?);
+                    output = Some(match arg.as_str() {
+                        "--ppm" => Output::Ppm(path),
+                        "--png" => Output::Png(path),
+                        _ => {
+                            let d = delay(args.peek());
+                            if d.is_some() {
+                                args.next();
+                            }
+                            Output::Gif(path, d.unwrap_or(Duration::from_millis(200)))
+                        }
+                    });
+                }
+                "--scale" => {
+                    scale = args.next()
+                        .and_then(|s| s.parse::<usize>().ok())
+                        .ok_or_else(|| format!("--scale needs a number. Options are {}", ANIMATOR_USAGE))?;
+                }
+                _ => rest.push(arg.clone()),
+            }
+        }
+        let output = output.unwrap_or(Output::Terminal(Duration::from_millis(200)));
+        Ok((Self::new(output, scale), rest))
+    }
+
+    /// The number of frames drawn so far
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn draw(&mut self, frame: &dyn Frame) -> io::Result<()> {
+        match &self.output {
+            Output::Terminal(delay) => {
+                let mut out = io::stdout();
+                if self.last_height > 0 {
+                    // Move the cursor back up to the top of the last frame
+                    write!(out, "\x1b[{}A", self.last_height + 1)?;
+                }
+                writeln!(out, "Generation {}", self.frames)?;
+                writeln!(out, "{}", frame.text())?;
+                out.flush()?;
+                self.last_height = frame.height();
+                thread::sleep(*delay);
+            }
+            Output::Ppm(dir) => {
+                fs::create_dir_all(dir)?;
+                let mut out = BufWriter::new(File::create(dir.join(format!("frame_{:04}.ppm", self.frames)))?);
+                write!(out, "P6\n{} {}\n255\n", frame.width() * self.scale, frame.height() * self.scale)?;
+                out.write_all(&frame.pixels(self.scale))?;
+                out.flush()?;
+            }
+            Output::Png(dir) => {
+                fs::create_dir_all(dir)?;
+                let out = BufWriter::new(File::create(dir.join(format!("frame_{:04}.png", self.frames)))?);
+                let (w, h) = ((frame.width() * self.scale) as u32, (frame.height() * self.scale) as u32);
+                let mut encoder = png::Encoder::new(out, w, h);
+                encoder.set_color(png::ColorType::RGB);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.write_header()
+                    .and_then(|mut writer| writer.write_image_data(&frame.pixels(self.scale)))
+                    .map_err(io::Error::other)?;
+            }
+            Output::Gif(path, delay) => {
+                let size = |cells: usize| {
+                    u16::try_from(cells * self.scale).map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "GIF frames can be at most 65535 pixels across")
+                    })
+                };
+                let (w, h) = (size(frame.width())?, size(frame.height())?);
+                if self.gif.is_none() {
+                    let palette = PALETTE.concat();
+                    let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), w, h, &palette)
+                        .map_err(io::Error::other)?;
+                    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+                    self.gif = Some(encoder);
+                }
+                let mut gif_frame = gif::Frame::from_indexed_pixels(w, h, &frame.palette_indices(self.scale), None);
+                // GIF delays are in hundredths of a second
+                gif_frame.delay = (delay.as_millis() / 10) as u16;
+                self.gif.as_mut().unwrap()
+                    .write_frame(&gif_frame)
+                    .map_err(io::Error::other)?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}